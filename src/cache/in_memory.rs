@@ -2,6 +2,7 @@ use dashmap::DashMap;
 
 use crate::dataclasses::Channel;
 
+#[allow(dead_code)]
 pub struct InMemoryCache<T> {
     channels: DashMap<String, Channel<T>>,
 }
@@ -1,56 +1,112 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Result};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 
 use crate::{
-    dataclasses::{HttpAttachable, Snowflake},
-    gateway::{Gateway, GatewayEvent, Intents, RawGatewayEvent, Rx},
+    dataclasses::{HttpAttachable, Presence, Snowflake},
+    gateway::{
+        error::event_data, Gateway, GatewayEncoding, GatewayEvent, GatewayUrl, GuildMembers,
+        Intents, MemberQuery, Rx, TransportCompression, VoiceConnectionInfo,
+    },
     http::client::HttpClient,
+    voice::{AudioPlayer, PlayerRx, PlayerTx, VoiceConnection},
 };
 
+use super::ClientEvent;
+
 /// Represents a high-level Discord client.
 pub struct Client {
     pub gateway: Arc<Mutex<Option<Gateway>>>,
     pub token: String,
     pub intents: Option<Intents>,
+    pub encoding: GatewayEncoding,
+    pub compression: Option<TransportCompression>,
+    pub presence: Option<Presence>,
+
+    /// Whether dispatches that aren't modelled are errors, instead of [`GatewayEvent::Unknown`].
+    pub strict: bool,
     pub rx: Option<Rx>,
     pub http: Arc<HttpClient>,
+
+    /// Track ends of the players created by the client, see [`Client::next_event`].
+    player_tx: PlayerTx,
+    player_rx: PlayerRx,
 }
 
 impl Client {
     pub fn new<K: ToString>(token: K, intents: Option<Intents>) -> Self {
+        let (player_tx, player_rx) = mpsc::unbounded_channel();
+
         Self {
             gateway: Arc::new(Mutex::new(None)),
             token: token.to_string(),
             intents,
+            encoding: GatewayEncoding::Json,
+            compression: None,
+            presence: None,
+            strict: false,
             rx: None,
             http: Arc::new(HttpClient::try_new(token).unwrap()),
+            player_tx,
+            player_rx,
         }
     }
 
+    /// Sets the payload encoding of the gateway connection.
+    pub fn with_encoding(mut self, encoding: GatewayEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Enables transport compression for the gateway connection.
+    pub fn with_compression(mut self, compression: TransportCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Fails on dispatches that aren't modelled, instead of returning [`GatewayEvent::Unknown`].
+    /// Useful in tests.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the presence to identify with.
+    pub fn with_presence(mut self, presence: Presence) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
     /// Connects to the gateway. This only registers a gateway object inside the client struct.
     ///
     /// # Example
-    /// ```rust
-    /// use omu::Intents;
+    /// ```rust,no_run
+    /// use omu::{Client, Intents};
+    /// # async fn example() -> anyhow::Result<()> {
     ///
     /// let mut client = Client::new("token".to_string(), Some(Intents::GUILD_MESSAGES));
     /// client.connect().await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn connect(&mut self) -> Result<()> {
-        let mut gateway =
-            Gateway::new_connection("wss://gateway.discord.gg/?v=10&encoding=json").await?;
+        let url = GatewayUrl {
+            encoding: self.encoding,
+            compression: self.compression,
+            ..Default::default()
+        };
+
+        let mut gateway = Gateway::new_connection(&url.to_string()).await?;
+        gateway.presence = self.presence.clone();
         gateway
             .authenticate(&self.token, self.intents.clone())
             .await?;
 
-        if let Some(data) = gateway.next().await? {
-            let event: RawGatewayEvent = data.into();
+        if let Some(event) = gateway.next().await? {
             match event.get_event_data()? {
-                GatewayEvent::Hello(hello) => {
-                    gateway.heartbeat_interval = Some(hello.heartbeat_interval);
-                }
+                // the heartbeat interval is tracked by the gateway, see `Gateway::heartbeat`
+                GatewayEvent::Hello(_) => {}
                 _ => {
                     return Err(anyhow!(
                         "unrecognized data type after authentication (expected GatewayEvent::Hello)"
@@ -70,15 +126,16 @@ impl Client {
 
     /// Iterates over the gateway and returns the next event data.
     /// Unlike `Gateway::next` (which returns a raw `Message`), this returns a `GatewayEvent`, a typed enum.
+    ///
+    /// Close frames the gateway recovers from are returned as [`GatewayEvent::Closed`].
+    /// If the gateway gives up reconnecting, the reason is returned as an error;
+    /// a [`GatewayError`](crate::gateway::GatewayError) when Discord closed the connection.
+    /// A payload that can't be decoded is also returned as an error, but doesn't stop the client.
     pub async fn next(&mut self) -> Result<GatewayEvent> {
         if let Some(rx) = self.rx.as_mut() {
-            if let Some(message) = rx.recv().await {
-                let event: RawGatewayEvent = message.into();
-
-                let mut data = event.get_event_data()?;
-                if let GatewayEvent::MessageCreate(mc) = &mut data {
-                    mc.message.attach(self.http.clone());
-                }
+            if let Some(event) = rx.recv().await {
+                let mut data = event_data(event, self.strict)?;
+                data.attach(self.http.clone());
 
                 return Ok(data);
             }
@@ -87,6 +144,39 @@ impl Client {
         Err(anyhow!("no data received"))
     }
 
+    /// Like [`Client::next`], but also returns the track ends of the players created by the
+    /// client, see [`Client::new_player`].
+    pub async fn next_event(&mut self) -> Result<ClientEvent> {
+        let Some(rx) = self.rx.as_mut() else {
+            return Err(anyhow!("no data received"));
+        };
+
+        tokio::select! {
+            event = rx.recv() => {
+                let event = event.ok_or_else(|| anyhow!("no data received"))?;
+                let mut data = event_data(event, self.strict)?;
+                data.attach(self.http.clone());
+
+                Ok(ClientEvent::Gateway(data))
+            }
+            Some(data) = self.player_rx.recv() => Ok(ClientEvent::TrackEnd(data)),
+        }
+    }
+
+    /// Starts an [`AudioPlayer`] on a voice connection, whose track ends are returned
+    /// by [`Client::next_event`].
+    pub fn new_player(&self, voice: Arc<VoiceConnection>) -> AudioPlayer {
+        AudioPlayer::new(voice, self.player_tx.clone())
+    }
+
+    /// The round-trip time of the last acknowledged heartbeat.
+    pub async fn latency(&self) -> Option<Duration> {
+        match self.gateway.lock().await.as_ref() {
+            Some(gw) => gw.latency().await,
+            None => None,
+        }
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         self.connect().await?;
 
@@ -100,18 +190,70 @@ impl Client {
         Ok(())
     }
 
+    /// Updates the presence of the bot.
+    pub async fn set_presence(&self, presence: Presence) -> Result<()> {
+        if let Some(gw) = self.gateway.lock().await.as_ref() {
+            gw.update_presence(presence).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Joins, moves between or leaves (with a `channel_id` of `None`) voice channels of a guild.
     pub async fn update_voice(
-        &mut self,
+        &self,
         guild_id: &Snowflake,
-        channel_id: &Snowflake,
+        channel_id: Option<Snowflake>,
         self_mute: bool,
         self_deaf: bool,
     ) -> Result<()> {
-        if let Some(gw) = self.gateway.lock().await.as_mut() {
+        if let Some(gw) = self.gateway.lock().await.as_ref() {
             gw.update_voice(guild_id, channel_id, self_mute, self_deaf)
                 .await?;
         }
 
         Ok(())
     }
+
+    /// Like [`Client::update_voice`], then waits for everything needed to connect to voice.
+    /// See [`Gateway::update_voice_and_wait`].
+    pub async fn update_voice_and_wait(
+        &self,
+        guild_id: &Snowflake,
+        channel_id: &Snowflake,
+        self_mute: bool,
+        self_deaf: bool,
+    ) -> Result<VoiceConnectionInfo> {
+        // the gateway is unlocked while waiting, so that it can be used meanwhile
+        let answer = match self.gateway.lock().await.as_ref() {
+            Some(gw) => {
+                gw.request_voice(guild_id, channel_id, self_mute, self_deaf)
+                    .await?
+            }
+            None => return Err(anyhow!("Not connected")),
+        };
+
+        answer.await
+    }
+
+    /// Requests members of a guild, and waits for them.
+    /// See [`Gateway::request_guild_members`].
+    pub async fn request_guild_members(
+        &self,
+        guild_id: &Snowflake,
+        query: MemberQuery,
+        limit: u32,
+        presences: bool,
+    ) -> Result<GuildMembers> {
+        // the gateway is unlocked while waiting, so that it can be used meanwhile
+        let answer = match self.gateway.lock().await.as_ref() {
+            Some(gw) => {
+                gw.request_members(guild_id, query, limit, presences)
+                    .await?
+            }
+            None => return Err(anyhow!("Not connected")),
+        };
+
+        answer.await
+    }
 }
@@ -0,0 +1,13 @@
+use crate::{gateway::GatewayEvent, voice::TrackEndData};
+
+/// An event of the [`Client`](super::Client), see [`Client::next_event`](super::Client::next_event).
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ClientEvent {
+    /// An event of the gateway, as returned by [`Client::next`](super::Client::next).
+    Gateway(GatewayEvent),
+
+    /// A track of an [`AudioPlayer`](crate::voice::AudioPlayer) created with
+    /// [`Client::new_player`](super::Client::new_player) stopped playing.
+    TrackEnd(TrackEndData),
+}
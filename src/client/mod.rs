@@ -1,2 +1,8 @@
 pub mod core;
 pub use core::Client;
+
+pub mod event;
+pub use event::ClientEvent;
+
+pub mod shard_manager;
+pub use shard_manager::ShardManager;
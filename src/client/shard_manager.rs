@@ -0,0 +1,276 @@
+use std::{collections::BTreeMap, ops::Range, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use futures_util::future::try_join_all;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::sleep,
+};
+
+use crate::{
+    dataclasses::{HttpAttachable, Presence, Snowflake},
+    gateway::{
+        error::event_data, get_sharding, Gateway, GatewayEncoding, GatewayEvent, GatewayUrl,
+        IdentifyLimiter, Intents, RawGatewayEvent, TransportCompression, VoiceConnectionInfo,
+    },
+    http::client::HttpClient,
+};
+
+type ShardTx = UnboundedSender<(u64, Result<RawGatewayEvent>)>;
+type ShardRx = UnboundedReceiver<(u64, Result<RawGatewayEvent>)>;
+
+/// Runs several shards, merging their events into one stream tagged with the shard id.
+///
+/// The gateway URL and the recommended number of shards come from `GET /gateway/bot`.
+/// Shards are started `max_concurrency` at a time, as each identify bucket
+/// (`shard_id % max_concurrency`) allows one identify every 5 seconds. The shards share
+/// an [`IdentifyLimiter`], which also spaces out the identifies sent when reconnecting.
+///
+/// # Example
+/// ```rust,no_run
+/// use omu::{Intents, ShardManager};
+/// # async fn example() -> anyhow::Result<()> {
+///
+/// let mut manager = ShardManager::new("token", Some(Intents::GUILD_MESSAGES));
+/// manager.run().await?;
+///
+/// while let Ok((shard_id, event)) = manager.next().await {
+///     println!("shard {shard_id}: {event:?}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct ShardManager {
+    pub token: String,
+    pub intents: Option<Intents>,
+    pub encoding: GatewayEncoding,
+    pub compression: Option<TransportCompression>,
+    pub presence: Option<Presence>,
+
+    /// Whether dispatches that aren't modelled are errors, instead of [`GatewayEvent::Unknown`].
+    pub strict: bool,
+
+    /// The shards to run, and the total number of shards.
+    /// Runs every recommended shard when `None`.
+    pub shards: Option<(Range<u64>, u64)>,
+
+    /// The running gateways, by shard id.
+    pub gateways: BTreeMap<u64, Gateway>,
+    pub http: Arc<HttpClient>,
+
+    tx: ShardTx,
+    rx: ShardRx,
+}
+
+impl ShardManager {
+    pub fn new<K: ToString>(token: K, intents: Option<Intents>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        Self {
+            token: token.to_string(),
+            intents,
+            encoding: GatewayEncoding::Json,
+            compression: None,
+            presence: None,
+            strict: false,
+            shards: None,
+            gateways: BTreeMap::new(),
+            http: Arc::new(HttpClient::try_new(token).unwrap()),
+            tx,
+            rx,
+        }
+    }
+
+    /// Only runs the shards in `range`, out of `total_shards`.
+    /// Useful to spread shards over several processes.
+    pub fn with_shards(mut self, range: Range<u64>, total_shards: u64) -> Self {
+        self.shards = Some((range, total_shards));
+        self
+    }
+
+    /// Sets the payload encoding of the gateway connections.
+    pub fn with_encoding(mut self, encoding: GatewayEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Enables transport compression for the gateway connections.
+    pub fn with_compression(mut self, compression: TransportCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Fails on dispatches that aren't modelled, instead of returning [`GatewayEvent::Unknown`].
+    /// Useful in tests.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the presence every shard identifies with.
+    pub fn with_presence(mut self, presence: Presence) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Starts the shards.
+    ///
+    /// If fewer sessions than shards may be started, waits for the session start limit
+    /// to reset first.
+    pub async fn run(&mut self) -> Result<()> {
+        let mut bot = self.http.get_gateway_bot().await?;
+        let (range, total_shards) = loop {
+            let (range, total_shards) = self.shards.clone().unwrap_or((0..bot.shards, bot.shards));
+
+            if range.end > total_shards {
+                return Err(anyhow!(
+                    "shards {range:?} out of bounds (total shards: {total_shards})"
+                ));
+            }
+
+            let limit = &bot.session_start_limit;
+            if limit.remaining >= range.end.saturating_sub(range.start) {
+                break (range, total_shards);
+            }
+
+            sleep(Duration::from_millis(limit.reset_after)).await;
+            // the recommended number of shards may have changed too
+            bot = self.http.get_gateway_bot().await?;
+        };
+
+        let url = GatewayUrl {
+            base: bot.url.clone(),
+            encoding: self.encoding,
+            compression: self.compression,
+            ..Default::default()
+        }
+        .to_string();
+
+        let max_concurrency = bot.session_start_limit.max_concurrency;
+        let limiter = Arc::new(IdentifyLimiter::new(max_concurrency));
+
+        // any `max_concurrency` consecutive shards are in different buckets,
+        // and each batch waits for the limiter before identifying
+        let shard_ids = range.clone().collect::<Vec<_>>();
+        let batch_size = max_concurrency.max(1) as usize;
+
+        for batch in shard_ids.chunks(batch_size) {
+            let gateways =
+                try_join_all(batch.iter().map(|shard_id| {
+                    self.start_shard(&url, *shard_id, total_shards, limiter.clone())
+                }))
+                .await?;
+
+            self.gateways.extend(batch.iter().copied().zip(gateways));
+        }
+
+        self.shards = Some((range, total_shards));
+
+        Ok(())
+    }
+
+    async fn start_shard(
+        &self,
+        url: &str,
+        shard_id: u64,
+        total_shards: u64,
+        limiter: Arc<IdentifyLimiter>,
+    ) -> Result<Gateway> {
+        let mut gateway = Gateway::new_connection(url)
+            .await?
+            .with_shard(shard_id, total_shards)
+            .with_identify_limiter(limiter);
+        gateway.presence = self.presence.clone();
+        gateway
+            .authenticate(&self.token, self.intents.clone())
+            .await?;
+
+        let mut rx = gateway.run().await?;
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if tx.send((shard_id, event)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(gateway)
+    }
+
+    /// Returns the next event of any shard, along with the shard id.
+    ///
+    /// Errors are the same as [`Client::next`](crate::Client::next).
+    pub async fn next(&mut self) -> Result<(u64, GatewayEvent)> {
+        let (shard_id, event) = self
+            .rx
+            .recv()
+            .await
+            .ok_or_else(|| anyhow!("no data received"))?;
+
+        let mut data =
+            event_data(event, self.strict).with_context(|| format!("shard {shard_id}"))?;
+        data.attach(self.http.clone());
+
+        Ok((shard_id, data))
+    }
+
+    /// The gateway of the shard receiving events of a guild.
+    pub fn shard_for(&self, guild_id: &Snowflake) -> Option<&Gateway> {
+        let (_, total_shards) = self.shards.as_ref()?;
+        let (shard_id, _) = get_sharding(*guild_id, *total_shards);
+        self.gateways.get(&shard_id)
+    }
+
+    /// Joins, moves between or leaves (with a `channel_id` of `None`) voice channels of a guild,
+    /// through the shard of the guild.
+    pub async fn update_voice(
+        &self,
+        guild_id: &Snowflake,
+        channel_id: Option<Snowflake>,
+        self_mute: bool,
+        self_deaf: bool,
+    ) -> Result<()> {
+        self.voice_shard(guild_id)?
+            .update_voice(guild_id, channel_id, self_mute, self_deaf)
+            .await
+    }
+
+    /// Like [`ShardManager::update_voice`], then waits for everything needed to connect to voice.
+    /// See [`Gateway::update_voice_and_wait`].
+    pub async fn update_voice_and_wait(
+        &self,
+        guild_id: &Snowflake,
+        channel_id: &Snowflake,
+        self_mute: bool,
+        self_deaf: bool,
+    ) -> Result<VoiceConnectionInfo> {
+        self.voice_shard(guild_id)?
+            .update_voice_and_wait(guild_id, channel_id, self_mute, self_deaf)
+            .await
+    }
+
+    fn voice_shard(&self, guild_id: &Snowflake) -> Result<&Gateway> {
+        self.shard_for(guild_id)
+            .ok_or_else(|| anyhow!("the shard of guild {guild_id} isn't running"))
+    }
+
+    /// Updates the presence of the bot on every shard.
+    pub async fn set_presence(&self, presence: Presence) -> Result<()> {
+        for gateway in self.gateways.values() {
+            gateway.update_presence(presence.clone()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Disconnects every shard.
+    pub async fn disconnect(&mut self) -> Result<()> {
+        for gateway in self.gateways.values_mut() {
+            gateway.disconnect().await.ok();
+        }
+        self.gateways.clear();
+
+        Ok(())
+    }
+}
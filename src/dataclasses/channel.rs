@@ -10,7 +10,7 @@ use crate::{
     http::{client::HttpClient, http_messages::PrepareCreateMessageBuilder},
 };
 
-use super::{HttpAttachable, Mentionable, Snowflake, User};
+use super::{HttpAttachable, Member, Mentionable, Snowflake, User};
 
 /// To convert this directly into the typed version of a channel, use [`Channel::into`].
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,7 +49,7 @@ pub struct Channel<T> {
 
     /// for guild channels: id of the parent category for a channel (each parent category can contain up to 50 channels);
     /// for threads: id of the text channel this thread was created
-    pub parent_id: Option<String>,
+    pub parent_id: Option<Snowflake>,
     pub last_pin_timestamp: Option<String>,
 
     pub rtc_region: Option<String>,
@@ -60,6 +60,10 @@ pub struct Channel<T> {
     /// an approximate count of users in a thread, stops counting at 50
     pub member_count: Option<u8>,
     pub thread_metadata: Option<ThreadMetadata>,
+
+    /// Thread member object for the current user, if they have joined the thread.
+    pub member: Option<ThreadMember>,
+
     pub default_auto_archive_duration: Option<AutoArchiveDuration>,
 
     pub permissions: Option<String>,
@@ -70,7 +74,7 @@ pub struct Channel<T> {
     pub total_message_sent: Option<usize>,
 
     pub available_tags: Option<Vec<ForumTag>>,
-    pub applied_tags: Option<Vec<String>>,
+    pub applied_tags: Option<Vec<Snowflake>>,
     pub default_reaction_emoji: Option<DefaultForumReactionEmoji>,
     pub default_thread_rate_limit_per_user: Option<usize>,
 
@@ -131,7 +135,7 @@ impl<'a> Channel<TextChannel> {
     }
 
     pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
-        PrepareCreateMessageBuilder::new(&self.http.as_ref().unwrap(), &self.id)
+        PrepareCreateMessageBuilder::new(self.http.as_ref().unwrap(), &self.id)
     }
 }
 
@@ -144,7 +148,7 @@ impl From<Channel<TextChannel>> for TextChannel {
 
 impl<'a> TextChannel {
     pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
-        PrepareCreateMessageBuilder::new(&self.http.as_ref().unwrap(), &self.id)
+        PrepareCreateMessageBuilder::new(self.http.as_ref().unwrap(), &self.id)
     }
 }
 
@@ -184,7 +188,7 @@ impl<'a> Channel<VoiceChannel> {
     }
 
     pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
-        PrepareCreateMessageBuilder::new(&self.http.as_ref().unwrap(), &self.id)
+        PrepareCreateMessageBuilder::new(self.http.as_ref().unwrap(), &self.id)
     }
 }
 
@@ -197,14 +201,125 @@ impl From<Channel<VoiceChannel>> for VoiceChannel {
 
 impl<'a> VoiceChannel {
     pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
-        PrepareCreateMessageBuilder::new(&self.http.as_ref().unwrap(), &self.id)
+        PrepareCreateMessageBuilder::new(self.http.as_ref().unwrap(), &self.id)
     }
 }
 
-#[derive(Debug)]
-pub struct Thread;
+/// A thread in a text, announcement, forum or media channel.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Thread {
+    #[serde(skip)]
+    http: Option<Arc<HttpClient>>,
+
+    pub id: Snowflake,
+    #[serde(rename = "type")]
+    pub type_: ChannelType,
+    pub guild_id: Snowflake,
+
+    /// The channel the thread was created in.
+    pub parent_id: Option<Snowflake>,
+
+    /// The creator of the thread.
+    pub owner_id: Option<Snowflake>,
+
+    pub name: String,
+    pub last_message_id: Option<Snowflake>,
+    pub last_pin_timestamp: Option<String>,
+    pub rate_limit_per_user: Option<usize>,
+
+    /// Number of messages (not including the initial message or deleted messages) in the thread.
+    pub message_count: Option<usize>,
+
+    /// An approximate count of users in the thread, stops counting at 50.
+    pub member_count: Option<usize>,
+
+    /// Number of messages ever sent in the thread, including deleted ones.
+    pub total_message_sent: Option<usize>,
+
+    pub thread_metadata: ThreadMetadata,
+
+    /// Thread member object for the current user, if they have joined the thread.
+    pub member: Option<ThreadMember>,
+
+    /// IDs of the tags applied to a thread in a forum or media channel.
+    pub applied_tags: Option<Vec<Snowflake>>,
+    pub flags: Option<ChannelFlags>,
+
+    /// Whether the thread was just created. Only in `THREAD_CREATE`.
+    pub newly_created: Option<bool>,
+}
+
+impl Channel<Thread> {
+    fn _to(self) -> Thread {
+        Thread {
+            http: self.http,
+            id: self.id,
+            type_: self.type_,
+            guild_id: self.guild_id.unwrap(),
+            parent_id: self.parent_id,
+            owner_id: self.owner_id,
+            name: self.name.unwrap(),
+            last_message_id: self.last_message_id,
+            last_pin_timestamp: self.last_pin_timestamp,
+            rate_limit_per_user: self.rate_limit_per_user,
+            message_count: self.message_count,
+            member_count: self.member_count.map(usize::from),
+            total_message_sent: self.total_message_sent,
+            thread_metadata: self.thread_metadata.unwrap(),
+            member: self.member,
+            applied_tags: self.applied_tags,
+            flags: self.flags,
+            newly_created: None,
+        }
+    }
+}
+
+impl From<Channel<Thread>> for Thread {
+    /// Converts directly into a typed thread.
+    fn from(value: Channel<Thread>) -> Self {
+        value._to()
+    }
+}
+
+impl<'a> Thread {
+    pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
+        PrepareCreateMessageBuilder::new(self.http.as_ref().unwrap(), &self.id)
+    }
+}
+
+impl HttpAttachable for Thread {
+    fn attach(&mut self, http: Arc<HttpClient>) {
+        self.http = Some(http);
+    }
+}
+
+impl Mentionable for Thread {
+    fn mention(&self) -> String {
+        self.id.mention_channel()
+    }
+}
+
+/// A user that has joined a thread.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadMember {
+    /// The ID of the thread. Omitted in `GUILD_CREATE`.
+    pub id: Option<Snowflake>,
+
+    /// The ID of the user. Omitted in `GUILD_CREATE`.
+    pub user_id: Option<Snowflake>,
+
+    /// When the user last joined the thread. (ISO8601 timestamp)
+    pub join_timestamp: String,
+
+    /// Notification settings of the user.
+    pub flags: u64,
+
+    /// The guild member, if requested.
+    pub member: Option<Member>,
+}
 
 bitflags! {
+    /// The type of a channel, as `1 << type`, so that types can be combined to match several.
     #[derive(Debug)]
     pub struct ChannelType: u64 {
         const GUILD_TEXT      = 1 << 0;
@@ -223,7 +338,41 @@ bitflags! {
     }
 }
 
-boilerplate_flags!(ChannelType);
+/// The type number, of a single type.
+impl From<ChannelType> for u64 {
+    fn from(value: ChannelType) -> u64 {
+        value.bits().trailing_zeros() as u64
+    }
+}
+
+impl Serialize for ChannelType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if self.bits().count_ones() != 1 {
+            return Err(serde::ser::Error::custom(format!(
+                "{self:?} isn't a single channel type"
+            )));
+        }
+        serializer.serialize_u64(self.bits().trailing_zeros() as u64)
+    }
+}
+
+/// Types that aren't known yet are kept as their bit, up to 63.
+impl<'de> Deserialize<'de> for ChannelType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value = u64::deserialize(deserializer)?;
+        u32::try_from(value)
+            .ok()
+            .and_then(|value| 1u64.checked_shl(value))
+            .map(ChannelType::from_bits_retain)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown channel type {value}")))
+    }
+}
 
 bitflags! {
     #[derive(Debug)]
@@ -329,7 +478,8 @@ impl<'de> Deserialize<'de> for Permissions {
         D: serde::Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(Permissions::from_bits_truncate(s.parse().unwrap()))
+        let bits = s.parse().map_err(serde::de::Error::custom)?;
+        Ok(Permissions::from_bits_truncate(bits))
     }
 }
 
@@ -389,3 +539,25 @@ pub enum ForumLayout {
     ListView = 1,
     GalleryView = 2,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_type_numbers() {
+        let forum = serde_json::from_str::<ChannelType>("15").unwrap();
+        assert_eq!(forum.bits(), ChannelType::GUILD_FORUM.bits());
+        assert_eq!(serde_json::to_string(&forum).unwrap(), "15");
+        assert_eq!(u64::from(forum), 15);
+
+        // an unknown type keeps its number
+        let unknown = serde_json::from_str::<ChannelType>("17").unwrap();
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), "17");
+        assert_eq!(u64::from(unknown), 17);
+
+        assert!(serde_json::from_str::<ChannelType>("64").is_err());
+        assert!(serde_json::to_string(&ChannelType::empty()).is_err());
+        assert!(serde_json::to_string(&(ChannelType::DM | ChannelType::GROUP_DM)).is_err());
+    }
+}
@@ -1 +1,49 @@
+use serde::{Deserialize, Deserializer};
+
 pub type HexCode = u32;
+
+/// Deserializes an integer sent either as a number or as a string.
+///
+/// ETF sends integers wider than 32 bits as big integers, which are decoded as strings
+/// (see [`crate::gateway::etf`]), so millisecond timestamps and flags can arrive either way.
+pub(crate) fn optional_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u64),
+        String(String),
+    }
+
+    match Option::<NumberOrString>::deserialize(deserializer)? {
+        Some(NumberOrString::Number(n)) => Ok(Some(n)),
+        Some(NumberOrString::String(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// (De)serializes colors sent as `"#rrggbb"` strings.
+pub(crate) mod hex_colors {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::HexCode;
+
+    pub fn serialize<S: Serializer>(colors: &[HexCode], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(colors.iter().map(|color| format!("#{color:06x}")))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<HexCode>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|color| {
+                HexCode::from_str_radix(color.trim_start_matches('#'), 16)
+                    .map_err(|_| D::Error::custom(format!("invalid color {color:?}")))
+            })
+            .collect()
+    }
+}
@@ -1,8 +1,209 @@
-use serde::{Serialize, Deserialize};
+use bitflags::bitflags;
+use ijson::IValue;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
+use crate::boilerplate_flags;
+
+use super::{Channel, Emoji, Member, PresenceUpdate, Role, Snowflake, Thread, VoiceState};
+
+/// A guild that isn't available, as in `READY` or `GUILD_DELETE`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PartialGuild {
-    /// Unavailable as this is partial.
+    pub id: Snowflake,
+
+    /// `true` if the guild is unavailable due to an outage.
+    /// In `GUILD_DELETE`, `false` means the bot was removed from the guild.
+    #[serde(default)]
     pub unavailable: bool,
-    pub id: String,
+}
+
+/// Represents a guild.
+///
+/// Fields from `joined_at` to `stage_instances` are only sent in `GUILD_CREATE`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Guild {
+    pub id: Snowflake,
+    pub name: String,
+
+    /// The icon hash.
+    pub icon: Option<String>,
+
+    /// The splash hash.
+    pub splash: Option<String>,
+
+    /// The discovery splash hash. Only present for guilds with the "DISCOVERABLE" feature.
+    pub discovery_splash: Option<String>,
+
+    pub owner_id: Snowflake,
+    pub afk_channel_id: Option<Snowflake>,
+
+    /// AFK timeout in seconds.
+    pub afk_timeout: u32,
+
+    pub widget_enabled: Option<bool>,
+    pub widget_channel_id: Option<Snowflake>,
+    pub verification_level: VerificationLevel,
+    pub default_message_notifications: DefaultMessageNotificationLevel,
+    pub explicit_content_filter: ExplicitContentFilterLevel,
+    pub roles: Vec<Role>,
+    pub emojis: Vec<Emoji>,
+
+    /// Enabled guild features, such as "COMMUNITY".
+    pub features: Vec<String>,
+
+    /// Required MFA level for the guild.
+    pub mfa_level: MfaLevel,
+
+    /// The application ID of the guild creator, if it is bot-created.
+    pub application_id: Option<Snowflake>,
+
+    /// The channel where guild notices such as welcome messages and boost events are posted.
+    pub system_channel_id: Option<Snowflake>,
+    pub system_channel_flags: SystemChannelFlags,
+
+    /// The channel where Community guilds can display rules and/or guidelines.
+    pub rules_channel_id: Option<Snowflake>,
+
+    pub max_presences: Option<u64>,
+    pub max_members: Option<u64>,
+    pub vanity_url_code: Option<String>,
+    pub description: Option<String>,
+
+    /// The banner hash.
+    pub banner: Option<String>,
+
+    pub premium_tier: PremiumTier,
+
+    /// The number of boosts this guild currently has.
+    pub premium_subscription_count: Option<u64>,
+
+    /// The preferred locale of a Community guild. Defaults to "en-US".
+    pub preferred_locale: String,
+
+    /// The channel where admins and moderators of Community guilds receive notices from Discord.
+    pub public_updates_channel_id: Option<Snowflake>,
+
+    pub max_video_channel_users: Option<u64>,
+    pub max_stage_video_channel_users: Option<u64>,
+    pub welcome_screen: Option<IValue>,
+    pub nsfw_level: NsfwLevel,
+    pub stickers: Option<Vec<IValue>>,
+
+    /// Whether the guild has the boost progress bar enabled.
+    pub premium_progress_bar_enabled: bool,
+
+    /// The channel where admins and moderators of Community guilds receive safety alerts from Discord.
+    pub safety_alerts_channel_id: Option<Snowflake>,
+
+    /// When the bot joined the guild. (ISO8601 timestamp)
+    pub joined_at: Option<String>,
+
+    /// Whether the guild is considered large.
+    pub large: Option<bool>,
+
+    pub unavailable: Option<bool>,
+    pub member_count: Option<u64>,
+    pub voice_states: Option<Vec<VoiceState>>,
+
+    /// Members of the guild. Only the bot itself and members in voice channels for large guilds.
+    pub members: Option<Vec<Member>>,
+    pub channels: Option<Vec<Channel<()>>>,
+
+    /// Active threads the bot has permission to view.
+    pub threads: Option<Vec<Thread>>,
+
+    /// Presences of the members, if the `GUILD_PRESENCES` intent is enabled.
+    pub presences: Option<Vec<PresenceUpdate>>,
+    pub stage_instances: Option<Vec<StageInstance>>,
+    pub guild_scheduled_events: Option<Vec<IValue>>,
+    pub soundboard_sounds: Option<Vec<IValue>>,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum VerificationLevel {
+    None = 0,
+    /// Verified email.
+    Low = 1,
+    /// Registered on Discord for longer than 5 minutes.
+    Medium = 2,
+    /// Member of the guild for longer than 10 minutes.
+    High = 3,
+    /// Verified phone number.
+    VeryHigh = 4,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum DefaultMessageNotificationLevel {
+    AllMessages = 0,
+    OnlyMentions = 1,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum ExplicitContentFilterLevel {
+    Disabled = 0,
+    MembersWithoutRoles = 1,
+    AllMembers = 2,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum MfaLevel {
+    None = 0,
+    Elevated = 1,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum PremiumTier {
+    None = 0,
+    Tier1 = 1,
+    Tier2 = 2,
+    Tier3 = 3,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum NsfwLevel {
+    Default = 0,
+    Explicit = 1,
+    Safe = 2,
+    AgeRestricted = 3,
+}
+
+bitflags! {
+    #[derive(Debug)]
+    pub struct SystemChannelFlags: u64 {
+        const SUPPRESS_JOIN_NOTIFICATIONS = 1 << 0;
+        const SUPPRESS_PREMIUM_SUBSCRIPTIONS = 1 << 1;
+        const SUPPRESS_GUILD_REMINDER_NOTIFICATIONS = 1 << 2;
+        const SUPPRESS_JOIN_NOTIFICATION_REPLIES = 1 << 3;
+        const SUPPRESS_ROLE_SUBSCRIPTION_PURCHASE_NOTIFICATIONS = 1 << 4;
+        const SUPPRESS_ROLE_SUBSCRIPTION_PURCHASE_NOTIFICATION_REPLIES = 1 << 5;
+    }
+}
+
+boilerplate_flags!(SystemChannelFlags);
+
+/// A live stage in a stage channel.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StageInstance {
+    pub id: Snowflake,
+    pub guild_id: Snowflake,
+    pub channel_id: Snowflake,
+
+    /// The topic of the stage. (1-120 characters)
+    pub topic: String,
+    pub privacy_level: StagePrivacyLevel,
+    pub guild_scheduled_event_id: Option<Snowflake>,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum StagePrivacyLevel {
+    Public = 1,
+    GuildOnly = 2,
 }
@@ -0,0 +1,67 @@
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+use crate::boilerplate_flags;
+
+use super::{Permissions, Snowflake, User};
+
+/// Represents a guild member.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Member {
+    /// The user this member represents. Not included in `MESSAGE_CREATE`.
+    pub user: Option<User>,
+
+    /// The member's guild nickname.
+    pub nick: Option<String>,
+
+    /// The member's guild avatar hash.
+    pub avatar: Option<String>,
+
+    /// The member's role IDs.
+    pub roles: Vec<Snowflake>,
+
+    /// When the member joined the guild. (ISO8601 timestamp)
+    pub joined_at: Option<String>,
+
+    /// When the member started boosting the guild. (ISO8601 timestamp)
+    pub premium_since: Option<String>,
+
+    /// Whether the member is deafened in voice channels.
+    pub deaf: Option<bool>,
+
+    /// Whether the member is muted in voice channels.
+    pub mute: Option<bool>,
+
+    pub flags: Option<MemberFlags>,
+
+    /// Whether the member has not yet passed the guild's Membership Screening requirements.
+    pub pending: Option<bool>,
+
+    /// Total permissions of the member in the channel, including overwrites.
+    /// Only included in interactions.
+    pub permissions: Option<Permissions>,
+
+    /// When the member's timeout will expire. (ISO8601 timestamp)
+    pub communication_disabled_until: Option<String>,
+}
+
+bitflags! {
+    #[derive(Debug)]
+    pub struct MemberFlags: u64 {
+        /// Member has left and rejoined the guild.
+        const DID_REJOIN = 1 << 0;
+        const COMPLETED_ONBOARDING = 1 << 1;
+        const BYPASSES_VERIFICATION = 1 << 2;
+        const STARTED_ONBOARDING = 1 << 3;
+
+        /// Member is a guest and can only access the voice channel they were invited to.
+        const IS_GUEST = 1 << 4;
+
+        const STARTED_HOME_ACTIONS = 1 << 5;
+        const COMPLETED_HOME_ACTIONS = 1 << 6;
+        const AUTOMOD_QUARANTINED_USERNAME = 1 << 7;
+        const DM_SETTINGS_UPSELL_ACKNOWLEDGED = 1 << 9;
+    }
+}
+
+boilerplate_flags!(MemberFlags);
@@ -11,9 +11,7 @@ use crate::{
     utils,
 };
 
-use super::{
-    Attachment, Channel, ChannelType, HexCode, HttpAttachable, Role, Snowflake, Thread, User,
-};
+use super::{Attachment, Channel, ChannelType, HexCode, HttpAttachable, Snowflake, Thread, User};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
@@ -50,7 +48,9 @@ pub struct Message {
     pub flags: Option<MessageFlags>,
     pub message_reference: Option<MessageReference>,
     pub message_snapshots: Option<Vec<MessageSnapshot>>,
-    pub referenced_message: Option<MessageReference>,
+
+    /// The message replied to, if it wasn't deleted.
+    pub referenced_message: Option<Box<PartialMessage>>,
     // interaction_metadata
     // interaction
     pub thread: Option<Channel<Thread>>,
@@ -59,7 +59,7 @@ pub struct Message {
 impl<'a> Message {
     //                   ↓↓ look, i have a reason for this.
     pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
-        PrepareCreateMessageBuilder::new(&self.client.as_ref().unwrap(), &self.channel_id)
+        PrepareCreateMessageBuilder::new(self.client.as_ref().unwrap(), &self.channel_id)
             .message_reference(MessageReference {
                 type_: MessageReferenceType::Default,
                 message_id: Some(self.id),
@@ -83,6 +83,82 @@ impl<'a> Message {
 
 impl HttpAttachable for Message {
     fn attach(&mut self, http: Arc<HttpClient>) {
+        if let Some(message) = &mut self.referenced_message {
+            message.attach(http.clone());
+        }
+        self.client = Some(http);
+    }
+}
+
+/// A message where only `id` and `channel_id` are guaranteed, as in `MESSAGE_UPDATE`.
+///
+/// Missing fields weren't changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartialMessage {
+    #[serde(skip)]
+    client: Option<Arc<HttpClient>>,
+
+    pub id: Snowflake,
+    pub channel_id: Snowflake,
+    pub author: Option<User>,
+    pub content: Option<String>,
+    pub timestamp: Option<String>,
+    pub edited_timestamp: Option<String>,
+    pub tts: Option<bool>,
+    pub mention_everyone: Option<bool>,
+    pub mentions: Option<Vec<User>>,
+    pub mention_roles: Option<Vec<Snowflake>>,
+    pub mention_channels: Option<Vec<mentions::ChannelMention>>,
+    pub attachments: Option<Vec<Attachment>>,
+    pub embeds: Option<Vec<embed::Embed>>,
+    pub reactions: Option<Vec<Reaction>>,
+    pub nonce: Option<Nounce>,
+    pub pinned: Option<bool>,
+    pub webhook_id: Option<Snowflake>,
+
+    #[serde(rename = "type")]
+    pub type_: Option<MessageType>,
+
+    pub activity: Option<MessageActivity>,
+    pub flags: Option<MessageFlags>,
+    pub message_reference: Option<MessageReference>,
+    pub message_snapshots: Option<Vec<MessageSnapshot>>,
+
+    /// The message replied to, if it wasn't deleted.
+    pub referenced_message: Option<Box<PartialMessage>>,
+    pub thread: Option<Channel<Thread>>,
+}
+
+impl<'a> PartialMessage {
+    /// Replies to the message. See [`Message::prepare_send`].
+    pub fn prepare_send(&'a self) -> PrepareCreateMessageBuilder<'a> {
+        PrepareCreateMessageBuilder::new(self.client.as_ref().unwrap(), &self.channel_id)
+            .message_reference(MessageReference {
+                type_: MessageReferenceType::Default,
+                message_id: Some(self.id),
+                channel_id: None,
+                guild_id: None,
+                fail_if_not_exists: Some(true),
+            })
+    }
+
+    pub async fn fetch_channel<T>(&self) -> Result<Channel<T>> {
+        if let Some(client) = &self.client {
+            let mut channel = client.get_channel(&self.channel_id).await?;
+            channel.attach(client.clone());
+
+            Ok(channel)
+        } else {
+            Err(anyhow::anyhow!("no client was attached"))
+        }
+    }
+}
+
+impl HttpAttachable for PartialMessage {
+    fn attach(&mut self, http: Arc<HttpClient>) {
+        if let Some(message) = &mut self.referenced_message {
+            message.attach(http.clone());
+        }
         self.client = Some(http);
     }
 }
@@ -269,7 +345,8 @@ pub struct Reaction {
     pub me_burst: bool,
     pub emoji: Emoji,
 
-    /// HEX colors used for super reaction
+    /// HEX colors used for super reaction, sent as "#ff0000".
+    #[serde(with = "super::hex_colors")]
     pub burst_colors: Vec<HexCode>,
 }
 
@@ -283,7 +360,8 @@ pub struct ReactionDetails {
 pub struct Emoji {
     pub id: Option<Snowflake>,
     pub name: Option<String>,
-    pub roles: Option<Vec<Role>>,
+    /// IDs of the roles allowed to use this emoji.
+    pub roles: Option<Vec<Snowflake>>,
     pub user: Option<User>,
 
     /// whether this emoji must be wrapped in colons
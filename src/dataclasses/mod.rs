@@ -4,20 +4,26 @@ pub mod attachment;
 pub mod channel;
 pub mod common;
 pub mod guild;
+pub mod member;
 pub mod message;
+pub mod presence;
 pub mod role;
 pub mod snowflake;
 pub mod user;
+pub mod voice;
 
 pub use attachment::*;
 pub use channel::*;
 pub use common::*;
 pub use guild::*;
+pub use member::*;
 pub use message::*;
 pub use message::{embed::*, mentions::*};
+pub use presence::*;
 pub use role::*;
 pub use snowflake::*;
 pub use user::*;
+pub use voice::*;
 
 pub(crate) use _traits::HttpAttachable;
 pub use _traits::Mentionable;
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use super::{optional_u64, Snowflake};
+
+/// Represents a presence: the status and activities of a user.
+///
+/// This is also what the bot sends with "Identify" or "Update Presence" (op code: 3).
+///
+/// # Example
+/// ```rust
+/// use omu::dataclasses::{Activity, Presence, Status};
+///
+/// let presence = Presence::new(Status::Idle).with_activity(Activity::watching("the stars"));
+/// assert_eq!(
+///     serde_json::to_string(&presence).unwrap(),
+///     r#"{"since":null,"activities":[{"name":"the stars","type":3}],"status":"idle","afk":false}"#
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Presence {
+    /// Unix time (in milliseconds) of when the client went idle, or `None` if it isn't idle.
+    #[serde(default, deserialize_with = "optional_u64")]
+    pub since: Option<u64>,
+    pub activities: Vec<Activity>,
+    pub status: Status,
+    pub afk: bool,
+}
+
+impl Presence {
+    pub fn new(status: Status) -> Self {
+        Self {
+            since: None,
+            activities: vec![],
+            status,
+            afk: false,
+        }
+    }
+
+    pub fn with_activity(mut self, activity: Activity) -> Self {
+        self.activities.push(activity);
+        self
+    }
+
+    /// Marks the client as AFK since `since` (Unix time in milliseconds).
+    pub fn with_afk(mut self, since: u64) -> Self {
+        self.since = Some(since);
+        self.afk = true;
+        self
+    }
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new(Status::Online)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Online,
+    /// Do Not Disturb.
+    Dnd,
+    Idle,
+    /// Shown as offline. Only for sending.
+    Invisible,
+    Offline,
+}
+
+/// A user's presence, as received in `PRESENCE_UPDATE` or with a guild.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceUpdate {
+    /// The user the presence is of. Only `id` is guaranteed.
+    pub user: PresenceUser,
+    pub guild_id: Option<Snowflake>,
+    pub status: Status,
+    pub activities: Vec<Activity>,
+    pub client_status: ClientStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceUser {
+    pub id: Snowflake,
+}
+
+/// The status of a user on each platform. Not present when offline on that platform.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientStatus {
+    pub desktop: Option<Status>,
+    pub mobile: Option<Status>,
+    pub web: Option<Status>,
+}
+
+/// Represents an activity, such as "Playing ...".
+///
+/// Bots may only send `name`, `type`, `state` and `url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activity {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub type_: ActivityType,
+
+    /// Stream URL, only for [`ActivityType::Streaming`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// Unix time (in milliseconds) of when the activity was added to the user's session.
+    #[serde(
+        default,
+        deserialize_with = "optional_u64",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created_at: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<ActivityTimestamps>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub application_id: Option<Snowflake>,
+
+    /// What the user is currently doing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+
+    /// The user's current party status, or the text of a custom status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+
+    /// The emoji of a custom status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<ActivityEmoji>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub party: Option<ActivityParty>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<ActivityAssets>,
+
+    /// Whether the activity is an instanced game session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<bool>,
+
+    #[serde(
+        default,
+        deserialize_with = "optional_u64",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub flags: Option<u64>,
+
+    /// Labels of the custom buttons shown in the rich presence. (max 2)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buttons: Option<Vec<String>>,
+}
+
+impl Activity {
+    pub fn new<K: ToString>(type_: ActivityType, name: K) -> Self {
+        Self {
+            name: name.to_string(),
+            type_,
+            url: None,
+            created_at: None,
+            timestamps: None,
+            application_id: None,
+            details: None,
+            state: None,
+            emoji: None,
+            party: None,
+            assets: None,
+            instance: None,
+            flags: None,
+            buttons: None,
+        }
+    }
+
+    /// "Playing {name}"
+    pub fn playing<K: ToString>(name: K) -> Self {
+        Self::new(ActivityType::Playing, name)
+    }
+
+    /// "Streaming {name}", linking to a Twitch or YouTube `url`.
+    pub fn streaming<K: ToString, U: ToString>(name: K, url: U) -> Self {
+        Self::new(ActivityType::Streaming, name).with_url(url)
+    }
+
+    /// "Listening to {name}"
+    pub fn listening<K: ToString>(name: K) -> Self {
+        Self::new(ActivityType::Listening, name)
+    }
+
+    /// "Watching {name}"
+    pub fn watching<K: ToString>(name: K) -> Self {
+        Self::new(ActivityType::Watching, name)
+    }
+
+    /// A custom status, showing `state`.
+    pub fn custom<K: ToString>(state: K) -> Self {
+        Self::new(ActivityType::Custom, "Custom Status").with_state(state)
+    }
+
+    /// "Competing in {name}"
+    pub fn competing<K: ToString>(name: K) -> Self {
+        Self::new(ActivityType::Competing, name)
+    }
+
+    pub fn with_url<K: ToString>(mut self, url: K) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    pub fn with_state<K: ToString>(mut self, state: K) -> Self {
+        self.state = Some(state.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum ActivityType {
+    Playing = 0,
+    Streaming = 1,
+    Listening = 2,
+    Watching = 3,
+    Custom = 4,
+    Competing = 5,
+
+    /// A type this library doesn't know about yet.
+    #[serde(other)]
+    Unknown = u8::MAX,
+}
+
+/// Unix times (in milliseconds) of when the activity started and ends.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityTimestamps {
+    #[serde(default, deserialize_with = "optional_u64")]
+    pub start: Option<u64>,
+    #[serde(default, deserialize_with = "optional_u64")]
+    pub end: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityParty {
+    pub id: Option<String>,
+
+    /// The party's current and maximum size.
+    pub size: Option<(u32, u32)>,
+}
+
+/// Images for the presence and their hover texts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityAssets {
+    pub large_image: Option<String>,
+    pub large_text: Option<String>,
+    pub small_image: Option<String>,
+    pub small_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEmoji {
+    pub name: String,
+    pub id: Option<Snowflake>,
+    pub animated: Option<bool>,
+}
@@ -1,4 +1,7 @@
-use std::{fmt::Debug, ops::Deref};
+use std::{
+    fmt::{Debug, Display},
+    ops::Deref,
+};
 
 use lexical::parse;
 use serde::{Deserialize, Serialize};
@@ -10,7 +13,7 @@ pub struct Snowflake {
 
 impl Debug for Snowflake {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
 
@@ -24,15 +27,15 @@ impl Snowflake {
     }
 
     pub fn mention_user(&self) -> String {
-        format!("<@{}>", self.id.to_string())
+        format!("<@{}>", self.id)
     }
 
     pub fn mention_role(&self) -> String {
-        format!("<@&{}>", self.id.to_string())
+        format!("<@&{}>", self.id)
     }
 
     pub fn mention_channel(&self) -> String {
-        format!("<#{}>", self.id.to_string())
+        format!("<#{}>", self.id)
     }
 }
 
@@ -58,18 +61,20 @@ impl From<Snowflake> for u64 {
     }
 }
 
-impl ToString for Snowflake {
-    fn to_string(&self) -> String {
+impl Display for Snowflake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut buf = itoa::Buffer::new();
-        buf.format(self.id).to_string()
+        f.write_str(buf.format(self.id))
     }
 }
 
-impl From<String> for Snowflake {
-    fn from(id: String) -> Self {
-        Snowflake {
-            id: parse(id.as_str()).unwrap(),
-        }
+impl TryFrom<String> for Snowflake {
+    type Error = lexical::Error;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        Ok(Snowflake {
+            id: parse(id.as_str())?,
+        })
     }
 }
 
@@ -86,7 +91,7 @@ impl<'de> Deserialize<'de> for Snowflake {
     {
         let s = String::deserialize(deserializer)?;
         Ok(Snowflake {
-            id: parse(s.as_str()).unwrap(),
+            id: parse(s.as_str()).map_err(serde::de::Error::custom)?,
         })
     }
 }
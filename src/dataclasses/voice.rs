@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Member, Snowflake};
+
+/// Represents a user's voice connection status.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceState {
+    pub guild_id: Option<Snowflake>,
+
+    /// The channel the user is connected to, `None` when disconnected.
+    pub channel_id: Option<Snowflake>,
+    pub user_id: Snowflake,
+    pub member: Option<Member>,
+    pub session_id: String,
+
+    /// Whether the user is deafened by the server.
+    pub deaf: bool,
+
+    /// Whether the user is muted by the server.
+    pub mute: bool,
+
+    pub self_deaf: bool,
+    pub self_mute: bool,
+
+    /// Whether the user is streaming using "Go Live".
+    pub self_stream: Option<bool>,
+    pub self_video: bool,
+
+    /// Whether the user's permission to speak is denied.
+    pub suppress: bool,
+
+    /// When the user requested to speak. (ISO8601 timestamp)
+    pub request_to_speak_timestamp: Option<String>,
+}
@@ -0,0 +1,106 @@
+/// Close codes sent by the gateway when it closes the connection.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GatewayCloseCode {
+    #[error("unknown error (4000)")]
+    UnknownError,
+    #[error("unknown opcode (4001)")]
+    UnknownOpcode,
+    #[error("decode error (4002)")]
+    DecodeError,
+    #[error("not authenticated (4003)")]
+    NotAuthenticated,
+    #[error("authentication failed (4004)")]
+    AuthenticationFailed,
+    #[error("already authenticated (4005)")]
+    AlreadyAuthenticated,
+    #[error("invalid sequence number (4007)")]
+    InvalidSequence,
+    #[error("rate limited (4008)")]
+    RateLimited,
+    #[error("session timed out (4009)")]
+    SessionTimedOut,
+    #[error("invalid shard (4010)")]
+    InvalidShard,
+    #[error("sharding required (4011)")]
+    ShardingRequired,
+    #[error("invalid api version (4012)")]
+    InvalidApiVersion,
+    #[error("invalid intents (4013)")]
+    InvalidIntents,
+    #[error("disallowed intents (4014)")]
+    DisallowedIntents,
+
+    /// Any other close code, including the standard websocket ones.
+    #[error("connection closed ({0})")]
+    Other(u16),
+}
+
+impl GatewayCloseCode {
+    /// Whether reconnecting is pointless, as the same session would be closed again.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::AuthenticationFailed
+                | Self::InvalidShard
+                | Self::ShardingRequired
+                | Self::InvalidApiVersion
+                | Self::InvalidIntents
+                | Self::DisallowedIntents
+        )
+    }
+
+    /// Whether the session can be resumed after reconnecting.
+    /// Otherwise, a fresh identify is required.
+    pub fn is_resumable(&self) -> bool {
+        match self {
+            Self::NotAuthenticated | Self::InvalidSequence | Self::SessionTimedOut => false,
+            // closing with 1000 or 1001 invalidates the session
+            Self::Other(code) => !matches!(code, 1000 | 1001),
+            code => !code.is_fatal(),
+        }
+    }
+}
+
+impl From<u16> for GatewayCloseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            4000 => Self::UnknownError,
+            4001 => Self::UnknownOpcode,
+            4002 => Self::DecodeError,
+            4003 => Self::NotAuthenticated,
+            4004 => Self::AuthenticationFailed,
+            4005 => Self::AlreadyAuthenticated,
+            4007 => Self::InvalidSequence,
+            4008 => Self::RateLimited,
+            4009 => Self::SessionTimedOut,
+            4010 => Self::InvalidShard,
+            4011 => Self::ShardingRequired,
+            4012 => Self::InvalidApiVersion,
+            4013 => Self::InvalidIntents,
+            4014 => Self::DisallowedIntents,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<GatewayCloseCode> for u16 {
+    fn from(value: GatewayCloseCode) -> u16 {
+        match value {
+            GatewayCloseCode::UnknownError => 4000,
+            GatewayCloseCode::UnknownOpcode => 4001,
+            GatewayCloseCode::DecodeError => 4002,
+            GatewayCloseCode::NotAuthenticated => 4003,
+            GatewayCloseCode::AuthenticationFailed => 4004,
+            GatewayCloseCode::AlreadyAuthenticated => 4005,
+            GatewayCloseCode::InvalidSequence => 4007,
+            GatewayCloseCode::RateLimited => 4008,
+            GatewayCloseCode::SessionTimedOut => 4009,
+            GatewayCloseCode::InvalidShard => 4010,
+            GatewayCloseCode::ShardingRequired => 4011,
+            GatewayCloseCode::InvalidApiVersion => 4012,
+            GatewayCloseCode::InvalidIntents => 4013,
+            GatewayCloseCode::DisallowedIntents => 4014,
+            GatewayCloseCode::Other(code) => code,
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use flate2::{Decompress, FlushDecompress};
+use zstd::stream::raw::{Decoder, InBuffer, Operation, OutBuffer};
+
+use super::url::query_param;
+
+/// Marks the end of a message in a `zlib-stream`.
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Transport compression of the gateway connection. (`compress` in the gateway URL)
+///
+/// The whole connection shares one compression context, so unlike payload compression
+/// every message benefits from the ones sent before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportCompression {
+    ZlibStream,
+    ZstdStream,
+}
+
+impl TransportCompression {
+    /// The value of the `compress` query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ZlibStream => "zlib-stream",
+            Self::ZstdStream => "zstd-stream",
+        }
+    }
+
+    /// Reads the `compress` query parameter of a gateway URL.
+    pub fn from_url(url: &str) -> Option<Self> {
+        match query_param(url, "compress")? {
+            "zlib-stream" => Some(Self::ZlibStream),
+            "zstd-stream" => Some(Self::ZstdStream),
+            _ => None,
+        }
+    }
+}
+
+/// A persistent decompression context for one connection.
+///
+/// Create a new one for every connection, as the context can't be carried over.
+pub enum Inflater {
+    Zlib {
+        decompress: Decompress,
+        buffer: Vec<u8>,
+    },
+    Zstd {
+        decoder: Decoder<'static>,
+    },
+}
+
+impl Inflater {
+    pub fn new(compression: TransportCompression) -> Result<Self> {
+        Ok(match compression {
+            TransportCompression::ZlibStream => Self::Zlib {
+                decompress: Decompress::new(true),
+                buffer: Vec::new(),
+            },
+            TransportCompression::ZstdStream => Self::Zstd {
+                decoder: Decoder::new()?,
+            },
+        })
+    }
+
+    /// Feeds one binary frame.
+    /// Returns the decompressed payload once a whole message has been received.
+    pub fn decompress(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>> {
+        let output = match self {
+            Self::Zlib { decompress, buffer } => {
+                buffer.extend_from_slice(frame);
+                if !buffer.ends_with(&ZLIB_SUFFIX) {
+                    return Ok(None);
+                }
+
+                let mut output = Vec::with_capacity(buffer.len() * 4);
+                let mut consumed = 0;
+
+                loop {
+                    let total_in = decompress.total_in();
+                    let total_out = decompress.total_out();
+                    let room = output.len() < output.capacity();
+                    decompress.decompress_vec(
+                        &buffer[consumed..],
+                        &mut output,
+                        FlushDecompress::Sync,
+                    )?;
+                    consumed += (decompress.total_in() - total_in) as usize;
+
+                    if consumed >= buffer.len() && output.len() < output.capacity() {
+                        break;
+                    }
+                    // a corrupt stream can stop consuming input without failing
+                    if room
+                        && decompress.total_in() == total_in
+                        && decompress.total_out() == total_out
+                    {
+                        buffer.clear();
+                        return Err(anyhow::anyhow!("zlib stream made no progress"));
+                    }
+                    output.reserve(buffer.len().max(1024));
+                }
+
+                buffer.clear();
+                output
+            }
+            Self::Zstd { decoder } => {
+                let mut input = InBuffer::around(frame);
+                let mut chunk = [0u8; 16 * 1024];
+                let mut output = Vec::new();
+
+                loop {
+                    let read = input.pos();
+                    let mut out = OutBuffer::around(&mut chunk[..]);
+                    decoder.run(&mut input, &mut out)?;
+                    let written = out.pos();
+                    output.extend_from_slice(&chunk[..written]);
+
+                    if input.pos() >= frame.len() && written < chunk.len() {
+                        break;
+                    }
+                    if input.pos() == read && written == 0 {
+                        return Err(anyhow::anyhow!("zstd stream made no progress"));
+                    }
+                }
+
+                output
+            }
+        };
+
+        Ok(Some(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compress, Compression, FlushCompress};
+    use zstd::stream::raw::Encoder;
+
+    use super::*;
+
+    /// Discord-like messages, sharing most of their content.
+    fn messages() -> Vec<Vec<u8>> {
+        (0..3)
+            .map(|s| {
+                format!(r#"{{"op":0,"s":{s},"t":"TYPING_START","d":{{"channel_id":"1093210345113464886","user_id":"278915124533624832","timestamp":1713096093}}}}"#)
+                    .into_bytes()
+            })
+            .collect()
+    }
+
+    /// Compresses each message with one context, flushing after each like the gateway.
+    fn zlib_stream(messages: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut compress = Compress::new(Compression::default(), true);
+        messages
+            .iter()
+            .map(|message| {
+                let mut output = Vec::with_capacity(message.len() + 64);
+                compress
+                    .compress_vec(message, &mut output, FlushCompress::Sync)
+                    .unwrap();
+                output
+            })
+            .collect()
+    }
+
+    fn zstd_stream(messages: &[Vec<u8>]) -> Vec<Vec<u8>> {
+        let mut encoder = Encoder::new(3).unwrap();
+        messages
+            .iter()
+            .map(|message| {
+                let mut buffer = vec![0; message.len() + 64];
+                let mut output = OutBuffer::around(&mut buffer[..]);
+                let mut input = InBuffer::around(message);
+                encoder.run(&mut input, &mut output).unwrap();
+                encoder.flush(&mut output).unwrap();
+                let written = output.pos();
+                buffer.truncate(written);
+                buffer
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zlib_waits_for_suffix() {
+        let messages = messages();
+        let frames = zlib_stream(&messages);
+        let mut inflater = Inflater::new(TransportCompression::ZlibStream).unwrap();
+
+        // one message split over several frames
+        let (first, rest) = frames[0].split_at(frames[0].len() / 2);
+        let (second, third) = rest.split_at(rest.len() - 2);
+        assert_eq!(inflater.decompress(first).unwrap(), None);
+        assert_eq!(inflater.decompress(second).unwrap(), None);
+        assert_eq!(inflater.decompress(third).unwrap().unwrap(), messages[0]);
+
+        // later messages rely on the context of the earlier ones
+        assert!(frames[1].len() < frames[0].len());
+        for (frame, message) in frames.iter().zip(&messages).skip(1) {
+            assert_eq!(inflater.decompress(frame).unwrap().as_ref(), Some(message));
+        }
+    }
+
+    #[test]
+    fn zstd_shares_context() {
+        let messages = messages();
+        let frames = zstd_stream(&messages);
+        let mut inflater = Inflater::new(TransportCompression::ZstdStream).unwrap();
+
+        assert!(frames[1].len() < frames[0].len());
+        for (frame, message) in frames.iter().zip(&messages) {
+            assert_eq!(inflater.decompress(frame).unwrap().as_ref(), Some(message));
+        }
+    }
+
+    #[test]
+    fn decompresses_large_messages() {
+        // many times the compressed size
+        let message = r#"{"op":0,"s":1,"t":"GUILD_CREATE","d":{"members":[]}}"#
+            .repeat(1024)
+            .into_bytes();
+
+        let mut zlib = Inflater::new(TransportCompression::ZlibStream).unwrap();
+        let frame = &zlib_stream(std::slice::from_ref(&message))[0];
+        assert_eq!(zlib.decompress(frame).unwrap().unwrap(), message);
+
+        let mut zstd = Inflater::new(TransportCompression::ZstdStream).unwrap();
+        let frame = &zstd_stream(std::slice::from_ref(&message))[0];
+        assert_eq!(zstd.decompress(frame).unwrap().unwrap(), message);
+    }
+}
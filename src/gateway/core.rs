@@ -1,221 +1,1261 @@
-use std::{borrow::Cow, sync::Arc, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use futures_util::{stream::StreamExt, SinkExt};
+use futures_util::{
+    stream::{SplitSink, SplitStream, StreamExt},
+    SinkExt,
+};
+use ijson::IValue;
+use rand::Rng;
 use tokio::{
     net::TcpStream,
     sync::{
         mpsc::{self, UnboundedReceiver, UnboundedSender},
-        Mutex,
+        oneshot, Mutex, Notify,
     },
-    time::interval,
+    time::{interval_at, sleep, timeout, Interval},
 };
 use tokio_tungstenite::{
     tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message},
     MaybeTlsStream, WebSocketStream,
 };
+use tokio_util::sync::CancellationToken;
+
+use crate::dataclasses::{Presence, Snowflake, VoiceState};
+
+use super::{
+    get_sharding, CommandRateLimiter, GatewayEncoding, GatewayError, GuildMembers,
+    GuildMembersChunkData, IdentifyConnectionProperty, IdentifyLimiter, Inflater, Intents,
+    MemberQuery, RawGatewayEvent, ReconnectPolicy, TransportCompression, VoiceConnectionInfo,
+    VoiceServerUpdateData,
+};
+
+pub type Tx = UnboundedSender<Result<RawGatewayEvent>>;
+pub type Rx = UnboundedReceiver<Result<RawGatewayEvent>>;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+type WsSource = SplitStream<WsStream>;
+
+/// The state needed to resume or re-identify a dropped session.
+#[derive(Debug, Default)]
+pub struct SessionState {
+    pub token: Option<String>,
+    pub intents: Option<u64>,
+    pub shard: Option<(Snowflake, u64)>,
+
+    /// The last presence set, restored when identifying again.
+    pub presence: Option<Presence>,
+
+    pub session_id: Option<String>,
+    pub resume_gateway_url: Option<String>,
+
+    /// The sequence number of the last dispatch received.
+    pub last_sequence_number: Option<u64>,
+
+    /// The ID of the bot's user, from `READY`.
+    pub user_id: Option<Snowflake>,
+
+    /// Spaces out the identifies of this session and the other shards sharing it.
+    pub identify_limiter: Arc<IdentifyLimiter>,
+}
+
+impl SessionState {
+    /// Creates the "Identify" payload for this session.
+    fn identify(&self) -> Result<RawGatewayEvent> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Not authenticated"))?;
+
+        Ok(RawGatewayEvent::new_identify(
+            token,
+            IdentifyConnectionProperty {
+                os: "linux".to_string(),
+                browser: "rust".to_string(),
+                device: "rust".to_string(),
+            },
+            Some(false),
+            Some(50),
+            self.shard,
+            self.presence.as_ref(),
+            self.intents,
+        ))
+    }
+
+    /// Waits for the turn of this shard to identify.
+    ///
+    /// Returns a future that doesn't borrow the session, to be awaited once it's unlocked.
+    fn identify_turn(&self) -> impl Future<Output = ()> {
+        let limiter = self.identify_limiter.clone();
+        let shard_id = self.shard.map_or(0, |(shard_id, _)| shard_id.as_u64());
+
+        async move { limiter.acquire(shard_id).await }
+    }
+
+    /// Whether a previous `READY` left enough to resume with.
+    fn can_resume(&self) -> bool {
+        self.token.is_some() && self.session_id.is_some() && self.resume_gateway_url.is_some()
+    }
+
+    /// Forgets the current session, so that the next connection identifies from scratch.
+    fn invalidate(&mut self) {
+        self.session_id = None;
+        self.resume_gateway_url = None;
+        self.last_sequence_number = None;
+    }
+
+    /// Updates the state from a received event.
+    fn track(&mut self, event: &RawGatewayEvent) {
+        if let Some(sequence) = event.sequence {
+            self.last_sequence_number = Some(sequence);
+        }
+
+        if event.t.as_deref() == Some("READY") {
+            if let Some(data) = &event.data {
+                self.session_id = data
+                    .get("session_id")
+                    .and_then(|v| v.as_string())
+                    .map(|v| v.to_string());
+                self.resume_gateway_url = data
+                    .get("resume_gateway_url")
+                    .and_then(|v| v.as_string())
+                    .map(|v| v.to_string());
+                self.user_id = data
+                    .get("user")
+                    .and_then(|v| v.get("id"))
+                    .and_then(|v| ijson::from_value(v).ok());
+            }
+        }
+    }
+}
+
+/// Heartbeat bookkeeping, used to measure latency and detect zombied connections.
+#[derive(Debug)]
+pub struct HeartbeatState {
+    pub interval: Duration,
+
+    /// When the last heartbeat was sent, if it hasn't been acknowledged yet.
+    pub pending_since: Option<Instant>,
+
+    /// The round-trip time of the last acknowledged heartbeat.
+    pub latency: Option<Duration>,
+}
+
+impl HeartbeatState {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pending_since: None,
+            latency: None,
+        }
+    }
+
+    /// Starts over on a new connection.
+    fn reset(&mut self, interval: Duration) {
+        self.interval = interval;
+        self.pending_since = None;
+    }
+
+    /// Handles a heartbeat ACK. (op code: 11)
+    pub(crate) fn acknowledge(&mut self) {
+        if let Some(sent) = self.pending_since.take() {
+            self.latency = Some(sent.elapsed());
+        }
+    }
+}
+
+/// A "Request Guild Members" waiting for its chunks.
+struct MemberRequest {
+    members: GuildMembers,
+
+    /// The `chunk_index` of every chunk received so far.
+    received: HashSet<u32>,
+    sender: oneshot::Sender<GuildMembers>,
+}
 
-use crate::dataclasses::Snowflake;
+/// A voice state update waiting for the voice state and voice server of the bot.
+struct VoiceRequest {
+    state: Option<VoiceState>,
+    server: Option<VoiceServerUpdateData>,
+    sender: oneshot::Sender<VoiceConnectionInfo>,
+}
+
+/// Commands waiting for the events they're answered with.
+#[derive(Default)]
+struct PendingRequests {
+    /// "Request Guild Members", by nonce.
+    members: HashMap<String, MemberRequest>,
+
+    /// "Update Voice State", by guild.
+    voice: HashMap<Snowflake, VoiceRequest>,
+}
+
+type Requests = Arc<Mutex<PendingRequests>>;
 
-use super::{get_sharding, Intents};
+/// How long to wait for the voice server after a voice state update.
+const VOICE_TIMEOUT: Duration = Duration::from_secs(10);
 
-pub type Tx = UnboundedSender<Message>;
-pub type Rx = UnboundedReceiver<Message>;
+/// How long to wait for every chunk of a "Request Guild Members".
+const MEMBERS_TIMEOUT: Duration = Duration::from_secs(30);
 
-pub enum Status {
-    Establishing,
-    Established,
-    Closed,
+/// Commands for the writer task.
+enum Command {
+    /// Writes to a new connection from now on.
+    Attach(WsSink),
+    /// Starts heartbeating at the interval given by "Hello".
+    Hello(Duration),
+    /// Sends a heartbeat right away.
+    Heartbeat,
+    /// Sends "Identify" or "Resume" on a new connection, ahead of the queued commands.
+    Handshake(Message),
+    /// Queues a command, sent as soon as the rate limit allows.
+    Send(Message),
+    /// Closes the current connection.
+    Close(CloseFrame<'static>),
 }
 
+/// A gateway connection.
+///
+/// Reading and writing happen on two separate tasks, so that sends, heartbeats and
+/// reads never wait on each other. Both are stopped by [`Gateway::disconnect`], or
+/// when the gateway is dropped.
 pub struct Gateway {
-    pub stream: Arc<Mutex<Option<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
-    pub status: Status,
-    pub heartbeat_interval: Option<u64>,
     pub sharding: Option<(Snowflake, u64)>,
-    pub last_sequence_number: Option<u64>,
+
+    /// The presence to identify with.
+    pub presence: Option<Presence>,
+
+    /// Spaces out identifies, see [`Gateway::with_identify_limiter`].
+    pub identify_limiter: Arc<IdentifyLimiter>,
+
+    pub session: Arc<Mutex<SessionState>>,
+    pub heartbeat: Arc<Mutex<HeartbeatState>>,
+
+    encoding: GatewayEncoding,
+    commands: UnboundedSender<Command>,
+    rx: Option<Rx>,
+
+    /// The number of commands waiting for the rate limit.
+    pending_commands: Arc<AtomicUsize>,
+
+    /// Commands waiting for the events they're answered with.
+    requests: Requests,
+
+    /// Notified to drop the connection and resume, e.g. when a heartbeat goes unacknowledged.
+    reconnect: Arc<Notify>,
+    cancel: CancellationToken,
 }
 
 impl Gateway {
     /// Connects to the gateway and returns a new [`Gateway`].
+    ///
+    /// The payload encoding and transport compression are chosen by the `encoding` and
+    /// `compress` parameters of `endpoint`, see [`GatewayUrl`](super::GatewayUrl).
+    ///
+    /// Uses the default [`ReconnectPolicy`]. See [`Gateway::new_connection_with_policy`].
     pub async fn new_connection(endpoint: &str) -> Result<Self> {
+        Self::new_connection_with_policy(endpoint, ReconnectPolicy::default()).await
+    }
+
+    /// Connects to the gateway and returns a new [`Gateway`], retrying dropped connections
+    /// following `policy`.
+    pub async fn new_connection_with_policy(
+        endpoint: &str,
+        policy: ReconnectPolicy,
+    ) -> Result<Self> {
         let (stream, _) = tokio_tungstenite::connect_async(endpoint).await?;
+        let (sink, source) = stream.split();
+
+        let encoding = GatewayEncoding::from_url(endpoint);
+        let session = Arc::new(Mutex::new(SessionState::default()));
+        let heartbeat = Arc::new(Mutex::new(HeartbeatState::new(Duration::from_millis(5000))));
+        let reconnect = Arc::new(Notify::new());
+        let requests = Requests::default();
+        let pending_commands = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+
+        let (commands, commands_rx) = mpsc::unbounded_channel::<Command>();
+        let (tx, rx) = mpsc::unbounded_channel::<Result<RawGatewayEvent>>();
+
+        tokio::spawn(Self::writer_task(
+            sink,
+            commands_rx,
+            encoding,
+            session.clone(),
+            heartbeat.clone(),
+            pending_commands.clone(),
+            reconnect.clone(),
+            cancel.clone(),
+        ));
+        tokio::spawn(Self::reader_task(
+            source,
+            commands.clone(),
+            encoding,
+            session.clone(),
+            heartbeat.clone(),
+            requests.clone(),
+            reconnect.clone(),
+            cancel.clone(),
+            endpoint.to_string(),
+            policy,
+            tx,
+        ));
 
         Ok(Self {
-            stream: Arc::new(Mutex::new(Some(stream))),
-            status: Status::Establishing,
-            heartbeat_interval: None,
             sharding: None,
-            last_sequence_number: None,
+            presence: None,
+            identify_limiter: Arc::default(),
+            session,
+            heartbeat,
+            encoding,
+            commands,
+            rx: Some(rx),
+            pending_commands,
+            requests,
+            reconnect,
+            cancel,
         })
     }
 
+    /// The sequence number of the last dispatch received.
+    pub async fn last_sequence_number(&self) -> Option<u64> {
+        self.session.lock().await.last_sequence_number
+    }
+
+    /// The round-trip time of the last acknowledged heartbeat.
+    pub async fn latency(&self) -> Option<Duration> {
+        self.heartbeat.lock().await.latency
+    }
+
+    /// The number of commands waiting to be sent, held back by the rate limit
+    /// or by a reconnection.
+    pub fn pending_commands(&self) -> usize {
+        self.pending_commands.load(Ordering::Relaxed)
+    }
+
     /// Sets the sharding for the gateway.
     ///
-    /// ```rust
+    /// ```rust,no_run
+    /// # use omu::{dataclasses::Snowflake, Gateway};
+    /// # async fn example() -> anyhow::Result<()> {
     /// let gateway: Gateway = Gateway::new_connection("wss://gateway.discord.gg/?v=10&encoding=json")
     ///     .await?
-    ///     .with_guild_sharding(123456789, 10);
+    ///     .with_guild_sharding(&Snowflake::new(123456789), 10);
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn with_guild_sharding(mut self, guild: &Snowflake, total_shards: u64) -> Self {
         self.sharding = Some(get_sharding(*guild, total_shards));
         self
     }
 
-    /// Disconnects from the gateway.
-    pub async fn disconnect(&mut self) -> Result<()> {
-        let mut stream = self.stream.lock().await;
-        if let Some(mut stream) = stream.take() {
-            stream
-                .close(Some(CloseFrame {
-                    code: CloseCode::Normal,
-                    reason: Cow::from("Disconnected"),
-                }))
-                .await?;
+    /// Sets the shard of the gateway, sent when authenticating.
+    ///
+    /// ```rust,no_run
+    /// # use omu::Gateway;
+    /// # async fn example() -> anyhow::Result<()> {
+    /// let gateway: Gateway = Gateway::new_connection("wss://gateway.discord.gg/?v=10&encoding=json")
+    ///     .await?
+    ///     .with_shard(0, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_shard(mut self, shard_id: u64, total_shards: u64) -> Self {
+        self.sharding = Some((Snowflake::new(shard_id), total_shards));
+        self
+    }
 
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Already disconnected"))
+    /// Sets the presence to identify with.
+    pub fn with_presence(mut self, presence: Presence) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Shares an [`IdentifyLimiter`] with other shards of the bot, so that their identifies,
+    /// including the ones sent when reconnecting, honor the `max_concurrency` buckets together.
+    pub fn with_identify_limiter(mut self, limiter: Arc<IdentifyLimiter>) -> Self {
+        self.identify_limiter = limiter;
+        self
+    }
+
+    /// Disconnects from the gateway, stopping the reader and writer tasks.
+    pub async fn disconnect(&mut self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Already disconnected"));
         }
+
+        self.commands
+            .send(Command::Close(CloseFrame {
+                code: CloseCode::Normal,
+                reason: Cow::from("Disconnected"),
+            }))
+            .ok();
+        self.cancel.cancel();
+
+        Ok(())
     }
 
-    /// Read one message at a time.
-    pub async fn next(&mut self) -> Result<Option<Message>> {
-        let mut stream = self.stream.lock().await;
-        if let Some(stream) = stream.as_mut() {
-            let (_, mut read) = stream.split();
-            let message = read.next().await;
-            if let Some(msg) = message {
-                Ok(Some(msg?))
-            } else {
-                Ok(None)
-            }
-        } else {
-            Err(anyhow::anyhow!("Already disconnected"))
+    /// Read one event at a time. Returns `None` once [`Gateway::run`] has taken the receiver.
+    ///
+    /// Close frames and undecodable payloads are returned as a [`GatewayError`];
+    /// the gateway keeps going unless [`GatewayError::is_fatal`].
+    pub async fn next(&mut self) -> Result<Option<RawGatewayEvent>> {
+        match self.rx.as_mut() {
+            Some(rx) => rx.recv().await.transpose(),
+            None => Ok(None),
         }
     }
 
-    /// Send a message.
-    pub async fn send(&mut self, message: Message) -> Result<()> {
-        let mut stream = self.stream.lock().await;
-        if let Some(stream) = stream.as_mut() {
-            let (mut write, _) = stream.split();
-            write.send(message).await?;
+    /// Send a message. Queued if it would exceed the rate limit, see [`CommandRateLimiter`].
+    pub async fn send(&self, message: Message) -> Result<()> {
+        self.commands
+            .send(Command::Send(message))
+            .map_err(|_| anyhow::anyhow!("Already disconnected"))
+    }
 
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Already disconnected"))
-        }
+    /// Sends an event, encoded with the encoding of the connection.
+    pub async fn send_event(&self, event: RawGatewayEvent) -> Result<()> {
+        self.send(self.encoding.encode(&event)?).await
     }
 
-    /// Sends a heartbeat ACK. (op code: 11)
-    pub async fn heartbeat(&mut self) -> Result<()> {
-        self.send(
-            super::event::RawGatewayEvent {
-                op_code: 11,
-                data: None,
-                sequence: None,
-                t: None,
-            }
-            .into(),
-        )
-        .await?;
-        Ok(())
+    /// Sends a heartbeat with the last sequence number. (op code: 1)
+    pub async fn heartbeat(&self) -> Result<()> {
+        self.commands
+            .send(Command::Heartbeat)
+            .map_err(|_| anyhow::anyhow!("Already disconnected"))
     }
 
     /// Authenticates with the gateway.
-    /// ```rust
+    /// ```rust,no_run
     /// use omu::Intents;
+    /// # use omu::Gateway;
+    /// # async fn example(mut gateway: Gateway) -> anyhow::Result<()> {
     ///
     /// // with intents
     /// gateway.authenticate("some token", Some(Intents::GUILD_MESSAGES | Intents::GUILD_MEMBERS)).await?;
     ///
     /// // without intents
     /// gateway.authenticate("some token", None).await?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub async fn authenticate(&mut self, token: &str, intents: Option<Intents>) -> Result<()> {
-        self.send(
-            super::event::RawGatewayEvent::new_identify(
-                token,
-                super::event::IdentifyConnectionProperty {
-                    os: "linux".to_string(),
-                    browser: "rust".to_string(),
-                    device: "rust".to_string(),
-                },
-                Some(false),
-                Some(50),
-                self.sharding,
-                None,
-                intents.map(|i| i.into()),
-            )
-            .into(),
-        )
-        .await?;
+        let (identify, turn) = {
+            let mut session = self.session.lock().await;
+            session.token = Some(token.to_string());
+            session.intents = intents.map(|i| i.into());
+            session.shard = self.sharding;
+            session.presence = self.presence.take();
+            session.identify_limiter = self.identify_limiter.clone();
+            (session.identify()?, session.identify_turn())
+        };
+
+        turn.await;
+        self.send_event(identify).await?;
+        Ok(())
+    }
+
+    /// Updates the presence of the bot. (op code: 3)
+    ///
+    /// ```rust,no_run
+    /// use omu::dataclasses::{Activity, Presence, Status};
+    /// # use omu::Gateway;
+    /// # async fn example(gateway: Gateway) -> anyhow::Result<()> {
+    ///
+    /// gateway
+    ///     .update_presence(Presence::new(Status::Dnd).with_activity(Activity::playing("chess")))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_presence(&self, presence: Presence) -> Result<()> {
+        self.send_event(RawGatewayEvent::new_presence_update(&presence))
+            .await?;
+        self.session.lock().await.presence = Some(presence);
+        Ok(())
+    }
+
+    /// Requests members of a guild, and waits for every `GUILD_MEMBERS_CHUNK` of the response.
+    /// (op code: 8)
+    ///
+    /// Fails if the chunks aren't all received within 30 seconds.
+    ///
+    /// ```rust,no_run
+    /// use omu::gateway::MemberQuery;
+    /// # use omu::{dataclasses::Snowflake, Gateway};
+    /// # async fn example(gateway: Gateway, guild_id: Snowflake) -> anyhow::Result<()> {
+    ///
+    /// let members = gateway
+    ///     .request_guild_members(&guild_id, MemberQuery::Query("ab".to_string()), 10, false)
+    ///     .await?
+    ///     .members;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn request_guild_members(
+        &self,
+        guild_id: &Snowflake,
+        query: MemberQuery,
+        limit: u32,
+        presences: bool,
+    ) -> Result<GuildMembers> {
+        self.request_members(guild_id, query, limit, presences)
+            .await?
+            .await
+    }
+
+    /// Sends the request of [`Gateway::request_guild_members`], and returns the wait for
+    /// its chunks, which doesn't borrow the gateway.
+    pub(crate) async fn request_members(
+        &self,
+        guild_id: &Snowflake,
+        query: MemberQuery,
+        limit: u32,
+        presences: bool,
+    ) -> Result<impl Future<Output = Result<GuildMembers>>> {
+        let nonce = format!("{:016x}", rand::random::<u64>());
+        let (sender, receiver) = oneshot::channel();
+
+        self.requests.lock().await.members.insert(
+            nonce.clone(),
+            MemberRequest {
+                members: GuildMembers::default(),
+                received: HashSet::new(),
+                sender,
+            },
+        );
+
+        let event =
+            RawGatewayEvent::new_request_guild_members(guild_id, &query, limit, presences, &nonce);
+        if let Err(err) = self.send_event(event).await {
+            self.requests.lock().await.members.remove(&nonce);
+            return Err(err);
+        }
+
+        let requests = self.requests.clone();
+
+        Ok(async move {
+            match timeout(MEMBERS_TIMEOUT, receiver).await {
+                Ok(Ok(members)) => Ok(members),
+                Ok(Err(_)) => Err(anyhow::anyhow!(
+                    "Disconnected before every member chunk was received"
+                )),
+                Err(_) => {
+                    requests.lock().await.members.remove(&nonce);
+                    Err(anyhow::anyhow!("Timed out waiting for the member chunks"))
+                }
+            }
+        })
+    }
+
+    /// Drops the connection, then reconnects to `resume_gateway_url` and resumes the session,
+    /// replaying missed events. (op code: 6)
+    ///
+    /// Identifies again instead if no `READY` has been received yet.
+    pub async fn resume(&self) -> Result<()> {
+        self.reconnect.notify_one();
         Ok(())
     }
 
+    /// Takes the receiver of incoming events.
     pub async fn run(&mut self) -> Result<Rx> {
-        let interval_ms = self.heartbeat_interval.unwrap_or(5000);
+        self.rx
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Already running"))
+    }
 
-        let (tx, rx) = mpsc::unbounded_channel::<Message>();
+    async fn send_heartbeat(
+        sink: &mut WsSink,
+        encoding: GatewayEncoding,
+        session: &Mutex<SessionState>,
+        heartbeat: &Mutex<HeartbeatState>,
+    ) -> Result<()> {
+        let sequence = session.lock().await.last_sequence_number;
 
-        tokio::spawn(Self::heartbeat_task(self.stream.clone(), interval_ms));
-        tokio::spawn(Self::receive_task(self.stream.clone(), tx));
+        // marked before sending, so that a quick ACK can't be missed
+        heartbeat
+            .lock()
+            .await
+            .pending_since
+            .get_or_insert_with(Instant::now);
 
-        Ok(rx)
+        sink.send(encoding.encode(&RawGatewayEvent::new_heartbeat(sequence))?)
+            .await?;
+        Ok(())
     }
 
-    async fn heartbeat_task(
-        stream: Arc<Mutex<Option<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
-        interval_ms: u64,
+    /// Adds a `GUILD_MEMBERS_CHUNK` to the request it answers, completing it after the last chunk.
+    async fn collect_member_chunk(requests: &Mutex<PendingRequests>, data: &IValue) {
+        let Some(nonce) = data.get("nonce").and_then(|v| v.as_string()) else {
+            return;
+        };
+
+        let mut requests = requests.lock().await;
+        let requests = &mut requests.members;
+        let Some(request) = requests.get_mut(nonce.as_str()) else {
+            return;
+        };
+
+        match ijson::from_value::<GuildMembersChunkData>(data) {
+            Ok(chunk) => {
+                let chunk_count = chunk.chunk_count;
+                // a chunk sent twice is only added once
+                if request.received.insert(chunk.chunk_index) {
+                    request.members.extend(chunk);
+                }
+
+                if !(0..chunk_count).all(|index| request.received.contains(&index)) {
+                    return;
+                }
+
+                if let Some(request) = requests.remove(nonce.as_str()) {
+                    request.sender.send(request.members).ok();
+                }
+            }
+            // dropping the request fails it
+            Err(_) => {
+                requests.remove(nonce.as_str());
+            }
+        }
+    }
+
+    /// Adds a `VOICE_STATE_UPDATE` or `VOICE_SERVER_UPDATE` of the bot to the request of its guild,
+    /// completing it once both are received.
+    async fn collect_voice_update(
+        requests: &Mutex<PendingRequests>,
+        user_id: Option<Snowflake>,
+        t: &str,
+        data: &IValue,
     ) {
-        let mut interval = interval(Duration::from_millis(interval_ms));
+        let Some(guild_id) = data
+            .get("guild_id")
+            .and_then(|v| ijson::from_value::<Snowflake>(v).ok())
+        else {
+            return;
+        };
 
-        loop {
-            let message = Message::Text(r#"{"op":1,"d":null}"#.to_string());
+        let mut requests = requests.lock().await;
+        let Some(request) = requests.voice.get_mut(&guild_id) else {
+            return;
+        };
 
-            {
-                let mut stream = stream.lock().await;
-                if let Some(stream) = stream.as_mut() {
-                    stream.send(message).await.ok();
+        match t {
+            "VOICE_STATE_UPDATE" => {
+                if let Ok(state) = ijson::from_value::<VoiceState>(data) {
+                    if Some(state.user_id) == user_id {
+                        request.state = Some(state);
+                    }
                 }
             }
+            _ => {
+                // without an endpoint the voice server is gone, and another update follows
+                if let Ok(server) = ijson::from_value::<VoiceServerUpdateData>(data) {
+                    if server.endpoint.is_some() {
+                        request.server = Some(server);
+                    }
+                }
+            }
+        }
+
+        if request.state.is_none() || request.server.is_none() {
+            return;
+        }
+
+        if let Some(VoiceRequest {
+            state: Some(state),
+            server: Some(server),
+            sender,
+        }) = requests.voice.remove(&guild_id)
+        {
+            sender
+                .send(VoiceConnectionInfo {
+                    guild_id,
+                    channel_id: state.channel_id,
+                    user_id: state.user_id,
+                    session_id: state.session_id,
+                    endpoint: server.endpoint.unwrap_or_default(),
+                    token: server.token,
+                })
+                .ok();
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn writer_task(
+        sink: WsSink,
+        mut commands: UnboundedReceiver<Command>,
+        encoding: GatewayEncoding,
+        session: Arc<Mutex<SessionState>>,
+        heartbeat: Arc<Mutex<HeartbeatState>>,
+        pending: Arc<AtomicUsize>,
+        reconnect: Arc<Notify>,
+        cancel: CancellationToken,
+    ) {
+        let mut sink = Some(sink);
+        let mut beat: Option<Interval> = None;
+        let mut limiter = CommandRateLimiter::default();
+        let mut queue: VecDeque<Message> = VecDeque::new();
+        // whether queued commands may be sent; a new connection must identify or resume first
+        let mut ready = true;
+
+        loop {
+            // sends what the rate limit allows, and waits for the rest
+            let mut wait = None;
+            if let Some(sink) = sink.as_mut().filter(|_| ready) {
+                while !queue.is_empty() {
+                    match limiter.try_acquire() {
+                        Ok(()) => {
+                            if let Some(message) = queue.pop_front() {
+                                sink.send(message).await.ok();
+                            }
+                        }
+                        Err(delay) => {
+                            wait = Some(delay);
+                            break;
+                        }
+                    }
+                }
+            }
+            pending.store(queue.len(), Ordering::Relaxed);
+
+            tokio::select! {
+                biased;
+
+                command = commands.recv() => match command {
+                    Some(Command::Attach(new_sink)) => {
+                        sink = Some(new_sink);
+                        beat = None;
+                        limiter.reset();
+                        ready = false;
+                    }
+                    Some(Command::Hello(period)) => {
+                        // the first heartbeat is jittered, so that bots don't all beat at once
+                        let jitter = period.mul_f64(rand::random::<f64>());
+                        beat = Some(interval_at(tokio::time::Instant::now() + jitter, period));
+                    }
+                    Some(Command::Heartbeat) => {
+                        if let Some(sink) = sink.as_mut() {
+                            limiter.acquire_reserved();
+                            Self::send_heartbeat(sink, encoding, &session, &heartbeat).await.ok();
+                        }
+                    }
+                    Some(Command::Handshake(message)) => {
+                        if let Some(sink) = sink.as_mut() {
+                            limiter.acquire_reserved();
+                            sink.send(message).await.ok();
+                            ready = true;
+                        }
+                    }
+                    // kept while disconnected, and sent once the new connection is ready
+                    Some(Command::Send(message)) => queue.push_back(message),
+                    Some(Command::Close(frame)) => {
+                        if let Some(mut sink) = sink.take() {
+                            sink.send(Message::Close(Some(frame))).await.ok();
+                            sink.close().await.ok();
+                        }
+                        beat = None;
+                    }
+                    None => return,
+                },
 
-            interval.tick().await;
+                _ = cancel.cancelled() => return,
+
+                _ = async {
+                    match beat.as_mut() {
+                        Some(beat) => {
+                            beat.tick().await;
+                        }
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let zombied = heartbeat.lock().await.pending_since.take().is_some();
+
+                    if zombied {
+                        // no ACK since the last heartbeat, so the connection is zombied
+                        beat = None;
+                        reconnect.notify_one();
+                    } else if let Some(sink) = sink.as_mut() {
+                        limiter.acquire_reserved();
+                        Self::send_heartbeat(sink, encoding, &session, &heartbeat).await.ok();
+                    }
+                }
+
+                _ = async {
+                    match wait {
+                        Some(wait) => sleep(wait).await,
+                        None => std::future::pending().await,
+                    }
+                } => {}
+            }
         }
     }
 
-    async fn receive_task(
-        stream: Arc<Mutex<Option<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
+    #[allow(clippy::too_many_arguments)]
+    async fn reader_task(
+        mut source: WsSource,
+        commands: UnboundedSender<Command>,
+        encoding: GatewayEncoding,
+        session: Arc<Mutex<SessionState>>,
+        heartbeat: Arc<Mutex<HeartbeatState>>,
+        requests: Requests,
+        reconnect: Arc<Notify>,
+        cancel: CancellationToken,
+        endpoint: String,
+        policy: ReconnectPolicy,
         tx: Tx,
     ) {
+        // consecutive reconnects without a dispatch in between
+        let mut attempt = 0;
+        // the "Resume" or "Identify" to send once "Hello" is received
+        let mut handshake: Option<RawGatewayEvent> = None;
+
+        let compression = TransportCompression::from_url(&endpoint);
+        let mut inflater = match compression.map(Inflater::new).transpose() {
+            Ok(inflater) => inflater,
+            Err(err) => {
+                tx.send(Err(err)).ok();
+                cancel.cancel();
+                return;
+            }
+        };
+
         loop {
-            let mut lock = stream.lock().await;
-            if let Some(ws_stream) = lock.as_mut() {
-                if let Some(Ok(msg)) = ws_stream.next().await {
-                    tx.send(msg).ok();
+            let message = tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = reconnect.notified() => None,
+                message = source.next() => Some(message),
+            };
+
+            let message = match (message, inflater.as_mut()) {
+                (Some(Some(Ok(Message::Binary(frame)))), Some(inflater)) => {
+                    match inflater.decompress(&frame) {
+                        Ok(Some(payload)) => Some(Some(Ok(Message::Binary(payload)))),
+                        // the rest of the message is in the next frames
+                        Ok(None) => continue,
+                        // the compression context is broken, so start over on a new connection
+                        Err(err) => {
+                            tx.send(Err(err)).ok();
+                            None
+                        }
+                    }
+                }
+                (message, _) => message,
+            };
+
+            let resumable = match message {
+                None => true,
+                Some(Some(Ok(msg @ (Message::Text(_) | Message::Binary(_))))) => {
+                    let event = match encoding.decode(msg) {
+                        Ok(event) => event,
+                        Err(err) => {
+                            tx.send(Err(err.into())).ok();
+                            continue;
+                        }
+                    };
+                    session.lock().await.track(&event);
+
+                    match event.op_code {
+                        0 => {
+                            attempt = 0;
+
+                            match (event.t.as_deref(), &event.data) {
+                                (Some("GUILD_MEMBERS_CHUNK"), Some(data)) => {
+                                    Self::collect_member_chunk(&requests, data).await;
+                                }
+                                (
+                                    Some(t @ ("VOICE_STATE_UPDATE" | "VOICE_SERVER_UPDATE")),
+                                    Some(data),
+                                ) => {
+                                    let user_id = session.lock().await.user_id;
+                                    Self::collect_voice_update(&requests, user_id, t, data).await;
+                                }
+                                _ => {}
+                            }
+                        }
+                        1 => {
+                            commands.send(Command::Heartbeat).ok();
+                        }
+                        10 => {
+                            if let Some(period) = event
+                                .data
+                                .as_ref()
+                                .and_then(|d| d.get("heartbeat_interval"))
+                                .and_then(|v| v.to_u64())
+                            {
+                                let period = Duration::from_millis(period);
+                                heartbeat.lock().await.reset(period);
+                                commands.send(Command::Hello(period)).ok();
+                            }
+
+                            match handshake.take().map(|h| encoding.encode(&h)).transpose() {
+                                Ok(Some(handshake)) => {
+                                    commands.send(Command::Handshake(handshake)).ok();
+                                }
+                                Ok(None) => {}
+                                Err(err) => {
+                                    tx.send(Err(err)).ok();
+                                }
+                            }
+                        }
+                        11 => heartbeat.lock().await.acknowledge(),
+                        _ => {}
+                    }
+
+                    let requested = match event.op_code {
+                        7 => Some(true),
+                        9 => Some(
+                            event
+                                .data
+                                .as_ref()
+                                .and_then(|d| d.to_bool())
+                                .unwrap_or(false),
+                        ),
+                        _ => None,
+                    };
+
+                    if tx.send(Ok(event)).is_err() {
+                        cancel.cancel();
+                        return;
+                    }
+
+                    let Some(resumable) = requested else {
+                        continue;
+                    };
+
+                    if !resumable {
+                        // a fresh identify must wait between 1 and 5 seconds
+                        let delay = rand::thread_rng().gen_range(1000..=5000);
+                        sleep(Duration::from_millis(delay)).await;
+                    }
+
+                    resumable
+                }
+                Some(Some(Ok(Message::Close(frame)))) => {
+                    let error = GatewayError::closed(frame.as_ref());
+                    let fatal = error.is_fatal();
+                    let resumable = match &error {
+                        GatewayError::Closed { code, .. } => {
+                            code.is_none_or(|code| code.is_resumable())
+                        }
+                        _ => true,
+                    };
+
+                    tx.send(Err(error.into())).ok();
+                    if fatal {
+                        cancel.cancel();
+                        return;
+                    }
+
+                    resumable
+                }
+                Some(Some(Err(_))) | Some(None) => true,
+                Some(Some(Ok(_))) => continue,
+            };
+
+            // closing with anything but 1000 or 1001 keeps the session resumable
+            commands
+                .send(Command::Close(CloseFrame {
+                    code: CloseCode::Restart,
+                    reason: Cow::from("Reconnecting"),
+                }))
+                .ok();
+
+            if cancel.is_cancelled() {
+                return;
+            }
+
+            // chunks of pending requests won't arrive on the new connection
+            requests.lock().await.members.clear();
+
+            match Self::reconnect(
+                &commands,
+                &session,
+                &endpoint,
+                &policy,
+                &mut attempt,
+                resumable,
+            )
+            .await
+            {
+                Ok((new_source, payload)) => {
+                    source = new_source;
+                    handshake = Some(payload);
+                    // the compression context doesn't carry over to the new connection
+                    inflater = match compression.map(Inflater::new).transpose() {
+                        Ok(inflater) => inflater,
+                        Err(err) => {
+                            tx.send(Err(err)).ok();
+                            cancel.cancel();
+                            return;
+                        }
+                    };
+                }
+                Err(err) => {
+                    tx.send(Err(err)).ok();
+                    cancel.cancel();
+                    return;
                 }
             }
         }
     }
+
+    /// Reconnects following `policy`, hands the write half to the writer task and returns
+    /// the read half, along with the "Resume" or "Identify" to send after "Hello".
+    async fn reconnect(
+        commands: &UnboundedSender<Command>,
+        session: &Mutex<SessionState>,
+        endpoint: &str,
+        policy: &ReconnectPolicy,
+        attempt: &mut u32,
+        resumable: bool,
+    ) -> Result<(WsSource, RawGatewayEvent)> {
+        loop {
+            if *attempt > 0 {
+                sleep(policy.delay(*attempt)).await;
+            }
+
+            let (url, payload, turn) = {
+                let mut session = session.lock().await;
+                if resumable && session.can_resume() {
+                    let query = endpoint
+                        .split_once('?')
+                        .map_or("v=10&encoding=json", |(_, query)| query);
+                    let url = session.resume_gateway_url.as_deref().unwrap_or_default();
+
+                    (
+                        format!("{}/?{}", url.trim_end_matches('/'), query),
+                        RawGatewayEvent::new_resume(
+                            session.token.as_deref().unwrap_or_default(),
+                            session.session_id.as_deref().unwrap_or_default(),
+                            session.last_sequence_number,
+                        ),
+                        None,
+                    )
+                } else {
+                    session.invalidate();
+                    (
+                        endpoint.to_string(),
+                        session.identify()?,
+                        Some(session.identify_turn()),
+                    )
+                }
+            };
+
+            if let Some(turn) = turn {
+                turn.await;
+            }
+
+            *attempt += 1;
+
+            match tokio_tungstenite::connect_async(url).await {
+                Ok((stream, _)) => {
+                    let (sink, source) = stream.split();
+                    commands
+                        .send(Command::Attach(sink))
+                        .map_err(|_| anyhow::anyhow!("Already disconnected"))?;
+
+                    return Ok((source, payload));
+                }
+                Err(err) => {
+                    if policy.max_attempts.is_some_and(|max| *attempt >= max) {
+                        return Err(err.into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Gateway {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
 }
 
 impl Gateway {
+    /// Joins, moves between or leaves (with a `channel_id` of `None`) voice channels of a guild.
     pub async fn update_voice(
-        &mut self,
+        &self,
         guild_id: &Snowflake,
-        channel_id: &Snowflake,
+        channel_id: Option<Snowflake>,
         self_mute: bool,
         self_deaf: bool,
     ) -> Result<()> {
-        self.send(
-            super::RawGatewayEvent::new_voice_state_update(
-                guild_id, channel_id, self_mute, self_deaf,
-            )
-            .into(),
-        )
+        self.send_event(RawGatewayEvent::new_voice_state_update(
+            guild_id, channel_id, self_mute, self_deaf,
+        ))
         .await?;
 
         Ok(())
     }
+
+    /// Like [`Gateway::update_voice`], then waits for the `VOICE_STATE_UPDATE` and
+    /// `VOICE_SERVER_UPDATE` of the bot, which hold everything needed to connect to voice.
+    /// Only for joining a channel, as no voice server is sent when leaving.
+    ///
+    /// ```rust,no_run
+    /// # use omu::{dataclasses::Snowflake, Gateway};
+    /// # async fn example(gateway: Gateway, guild_id: Snowflake, channel_id: Snowflake) -> anyhow::Result<()> {
+    /// let info = gateway
+    ///     .update_voice_and_wait(&guild_id, &channel_id, false, true)
+    ///     .await?;
+    /// println!("connect to {} with {}", info.endpoint, info.session_id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_voice_and_wait(
+        &self,
+        guild_id: &Snowflake,
+        channel_id: &Snowflake,
+        self_mute: bool,
+        self_deaf: bool,
+    ) -> Result<VoiceConnectionInfo> {
+        self.request_voice(guild_id, channel_id, self_mute, self_deaf)
+            .await?
+            .await
+    }
+
+    /// Sends the voice state update of [`Gateway::update_voice_and_wait`], and returns
+    /// the wait for its answer, which doesn't borrow the gateway.
+    pub(crate) async fn request_voice(
+        &self,
+        guild_id: &Snowflake,
+        channel_id: &Snowflake,
+        self_mute: bool,
+        self_deaf: bool,
+    ) -> Result<impl Future<Output = Result<VoiceConnectionInfo>>> {
+        let (sender, receiver) = oneshot::channel();
+        self.requests.lock().await.voice.insert(
+            *guild_id,
+            VoiceRequest {
+                state: None,
+                server: None,
+                sender,
+            },
+        );
+
+        if let Err(err) = self
+            .update_voice(guild_id, Some(*channel_id), self_mute, self_deaf)
+            .await
+        {
+            self.requests.lock().await.voice.remove(guild_id);
+            return Err(err);
+        }
+
+        let requests = self.requests.clone();
+        let guild_id = *guild_id;
+
+        Ok(async move {
+            match timeout(VOICE_TIMEOUT, receiver).await {
+                Ok(Ok(info)) => Ok(info),
+                Ok(Err(_)) => Err(anyhow::anyhow!(
+                    "Connection closed before the voice server was received"
+                )),
+                Err(_) => {
+                    requests.lock().await.voice.remove(&guild_id);
+                    Err(anyhow::anyhow!("Timed out waiting for the voice server"))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use tokio::{net::TcpListener, sync::mpsc::UnboundedReceiver, task::yield_now, time::advance};
+
+    use super::*;
+
+    /// A gateway that acknowledges heartbeats, and forwards the op codes of other payloads.
+    async fn mock_server() -> (String, UnboundedReceiver<u64>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "ws://{}/?v=10&encoding=json",
+            listener.local_addr().unwrap()
+        );
+        let (ops, ops_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            // the first heartbeat is jittered over the interval, so it's made long enough
+            // not to fall within the test
+            let hello = json!({"op": 10, "d": {"heartbeat_interval": 10_000_000_000u64}});
+            ws.send(Message::Text(hello.to_string())).await.unwrap();
+
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                let op = serde_json::from_str::<Value>(&text).unwrap()["op"]
+                    .as_u64()
+                    .unwrap();
+                if op == 1 {
+                    let ack = json!({"op": 11});
+                    ws.send(Message::Text(ack.to_string())).await.unwrap();
+                } else {
+                    ops.send(op).ok();
+                }
+            }
+        });
+
+        (url, ops_rx)
+    }
+
+    /// The next op code, waited for without idling, as paused time jumps to the next timer
+    /// when idle.
+    async fn next_op(ops: &mut UnboundedReceiver<u64>) -> u64 {
+        loop {
+            if let Ok(op) = ops.try_recv() {
+                return op;
+            }
+            yield_now().await;
+        }
+    }
+
+    async fn wait_pending(gateway: &Gateway, pending: usize) {
+        while gateway.pending_commands() != pending {
+            yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queues_commands_over_rate_limit() {
+        let (url, mut ops) = mock_server().await;
+        let mut gateway = Gateway::new_connection(&url).await.unwrap();
+        gateway.authenticate("token", None).await.unwrap();
+        let _rx = gateway.run().await.unwrap();
+        assert_eq!(next_op(&mut ops).await, 2);
+
+        // "Identify" took a token too, which leaves 116 of the 117 for other commands
+        for _ in 0..120 {
+            gateway.update_presence(Presence::default()).await.unwrap();
+        }
+        for _ in 0..116 {
+            assert_eq!(next_op(&mut ops).await, 3);
+        }
+        wait_pending(&gateway, 4).await;
+
+        advance(Duration::from_secs(59)).await;
+        for _ in 0..100 {
+            yield_now().await;
+        }
+        assert!(ops.try_recv().is_err());
+        assert_eq!(gateway.pending_commands(), 4);
+
+        // the tokens come back a minute after they were taken
+        advance(Duration::from_secs(1)).await;
+        for _ in 0..4 {
+            assert_eq!(next_op(&mut ops).await, 3);
+        }
+        wait_pending(&gateway, 0).await;
+    }
 }
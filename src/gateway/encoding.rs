@@ -0,0 +1,57 @@
+use anyhow::Result;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::{etf, url::query_param, GatewayError, RawGatewayEvent};
+
+/// Payload encoding of the gateway connection. (`encoding` in the gateway URL)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GatewayEncoding {
+    #[default]
+    Json,
+    /// Erlang External Term Format. Smaller payloads, cheaper to parse. See [`etf`].
+    Etf,
+}
+
+impl GatewayEncoding {
+    /// The value of the `encoding` query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Etf => "etf",
+        }
+    }
+
+    /// Reads the `encoding` query parameter of a gateway URL, defaulting to JSON.
+    pub fn from_url(url: &str) -> Self {
+        match query_param(url, "encoding") {
+            Some("etf") => Self::Etf,
+            _ => Self::Json,
+        }
+    }
+
+    /// Decodes a received (and already decompressed) message.
+    pub fn decode(&self, message: Message) -> Result<RawGatewayEvent, GatewayError> {
+        match (self, message) {
+            (Self::Json, message) => RawGatewayEvent::try_from(message),
+            (Self::Etf, Message::Binary(bytes)) => {
+                let decoded = etf::decode(&bytes)
+                    .and_then(|value| Ok(ijson::from_value::<RawGatewayEvent>(&value)?));
+
+                decoded.map_err(|source| GatewayError::Decode {
+                    source: source.into(),
+                    raw: Message::Binary(bytes),
+                })
+            }
+            (Self::Etf, Message::Close(frame)) => Err(GatewayError::closed(frame.as_ref())),
+            (Self::Etf, message) => Err(GatewayError::UnexpectedFrame(message)),
+        }
+    }
+
+    /// Encodes an event to send.
+    pub fn encode(&self, event: &RawGatewayEvent) -> Result<Message> {
+        Ok(match self {
+            Self::Json => Message::Text(serde_json::to_string(event)?),
+            Self::Etf => Message::Binary(etf::encode(&ijson::to_value(event)?)?),
+        })
+    }
+}
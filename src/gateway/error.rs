@@ -0,0 +1,81 @@
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
+
+use super::{GatewayCloseCode, GatewayEvent, RawGatewayEvent};
+
+/// Errors of reading from the gateway.
+#[derive(thiserror::Error, Debug)]
+pub enum GatewayError {
+    /// The connection was closed. The gateway reconnects on its own, unless `code` is fatal.
+    #[error("connection closed ({code:?}): {reason}")]
+    Closed {
+        code: Option<GatewayCloseCode>,
+        reason: String,
+    },
+
+    /// A payload could not be decoded.
+    #[error("failed to decode payload: {source}")]
+    Decode {
+        source: Box<dyn std::error::Error + Send + Sync>,
+        /// The message as received (after decompression).
+        raw: Message,
+    },
+
+    /// A websocket frame that carries no payload, such as a ping.
+    #[error("unexpected frame: {0:?}")]
+    UnexpectedFrame(Message),
+
+    /// An op code that can't be received, or isn't known.
+    #[error("unexpected op code {op_code} (t: {t:?})")]
+    UnexpectedOpCode { op_code: u32, t: Option<String> },
+
+    /// A dispatch that isn't modelled, in strict mode.
+    #[error("unknown event {0}")]
+    UnknownEvent(String),
+
+    /// A payload missing a field required by its op code.
+    #[error("missing field `{field}` (op code {op_code})")]
+    MissingField { op_code: u32, field: &'static str },
+}
+
+impl GatewayError {
+    pub(crate) fn closed(frame: Option<&CloseFrame>) -> Self {
+        Self::Closed {
+            code: frame.map(|frame| u16::from(frame.code).into()),
+            reason: frame
+                .map(|frame| frame.reason.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether the gateway gave up on the connection.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, Self::Closed { code: Some(code), .. } if code.is_fatal())
+    }
+
+    /// [`GatewayEvent::Closed`] for a close frame the gateway recovers from.
+    pub fn as_event(&self) -> Option<GatewayEvent> {
+        match self {
+            Self::Closed { code, reason } if !self.is_fatal() => Some(GatewayEvent::Closed {
+                code: *code,
+                reason: reason.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes an event read from the gateway, turning close frames that the gateway
+/// recovers from into [`GatewayEvent::Closed`].
+pub(crate) fn event_data(
+    event: anyhow::Result<RawGatewayEvent>,
+    strict: bool,
+) -> anyhow::Result<GatewayEvent> {
+    match event {
+        Ok(event) if strict => event.get_event_data_strict(),
+        Ok(event) => event.get_event_data(),
+        Err(err) => err
+            .downcast_ref::<GatewayError>()
+            .and_then(GatewayError::as_event)
+            .ok_or(err),
+    }
+}
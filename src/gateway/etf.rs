@@ -0,0 +1,442 @@
+//! Erlang External Term Format, used by the gateway with `encoding=etf`.
+//!
+//! Terms are converted from and to [`IValue`], so that the rest of the library
+//! sees the same values as with JSON:
+//! * atoms `nil`, `true` and `false` become `null`, `true` and `false`, other atoms become strings.
+//! * binaries become strings.
+//! * big integers become strings, so that snowflakes look like they do in JSON.
+//!   Millisecond timestamps and flags are big integers too, the fields holding them
+//!   accept both strings and numbers.
+//!
+//! ```rust
+//! use omu::gateway::{etf, GatewayEvent, RawGatewayEvent};
+//!
+//! // {op: 10, d: {heartbeat_interval: 41250}, s: nil, t: nil}
+//! let mut payload = vec![131, 116, 0, 0, 0, 4, 119, 2, b'o', b'p', 97, 10];
+//! payload.extend_from_slice(&[119, 1, b'd', 116, 0, 0, 0, 1, 119, 18]);
+//! payload.extend_from_slice(b"heartbeat_interval");
+//! payload.extend_from_slice(&[98, 0, 0, 161, 34]);
+//! payload.extend_from_slice(&[119, 1, b's', 119, 3, b'n', b'i', b'l']);
+//! payload.extend_from_slice(&[119, 1, b't', 119, 3, b'n', b'i', b'l']);
+//!
+//! let value = etf::decode(&payload).unwrap();
+//! let event = ijson::from_value::<RawGatewayEvent>(&value).unwrap();
+//! match event.get_event_data().unwrap() {
+//!     GatewayEvent::Hello(hello) => assert_eq!(hello.heartbeat_interval, 41250),
+//!     _ => unreachable!(),
+//! }
+//!
+//! assert_eq!(etf::decode(&etf::encode(&value).unwrap()).unwrap(), value);
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use ijson::{DestructuredRef, IArray, INumber, IObject, IValue};
+
+const VERSION: u8 = 131;
+
+const NEW_FLOAT_EXT: u8 = 70;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const FLOAT_EXT: u8 = 99;
+const ATOM_EXT: u8 = 100;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const SMALL_ATOM_EXT: u8 = 115;
+const MAP_EXT: u8 = 116;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+/// Decodes an ETF payload.
+pub fn decode(bytes: &[u8]) -> Result<IValue> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    let version = reader.u8()?;
+    if version != VERSION {
+        bail!("unsupported ETF version {version}");
+    }
+
+    reader.term()
+}
+
+/// Encodes a value into an ETF payload.
+pub fn encode(value: &IValue) -> Result<Vec<u8>> {
+    let mut out = vec![VERSION];
+    write_term(&mut out, value)?;
+    Ok(out)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("unexpected end of ETF payload"))?;
+
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn term(&mut self) -> Result<IValue> {
+        let value = match self.u8()? {
+            SMALL_INTEGER_EXT => self.u8()?.into(),
+            INTEGER_EXT => i32::from_be_bytes(self.take(4)?.try_into()?).into(),
+            NEW_FLOAT_EXT => f64::from_be_bytes(self.take(8)?.try_into()?).into(),
+            FLOAT_EXT => {
+                let text = std::str::from_utf8(self.take(31)?)?;
+                text.trim_end_matches('\0').trim().parse::<f64>()?.into()
+            }
+            ATOM_EXT => {
+                let len = self.u16()? as usize;
+                atom(&latin1(self.take(len)?))
+            }
+            SMALL_ATOM_EXT => {
+                let len = self.u8()? as usize;
+                atom(&latin1(self.take(len)?))
+            }
+            ATOM_UTF8_EXT => {
+                let len = self.u16()? as usize;
+                atom(std::str::from_utf8(self.take(len)?)?)
+            }
+            SMALL_ATOM_UTF8_EXT => {
+                let len = self.u8()? as usize;
+                atom(std::str::from_utf8(self.take(len)?)?)
+            }
+            SMALL_TUPLE_EXT => {
+                let arity = self.u8()? as usize;
+                self.array(arity)?
+            }
+            LARGE_TUPLE_EXT => {
+                let arity = self.u32()? as usize;
+                self.array(arity)?
+            }
+            NIL_EXT => IArray::new().into(),
+            STRING_EXT => {
+                let len = self.u16()? as usize;
+                latin1(self.take(len)?).into()
+            }
+            LIST_EXT => {
+                let len = self.u32()? as usize;
+                let list = self.array(len)?;
+                // the tail of a proper list is NIL_EXT
+                self.term()?;
+                list
+            }
+            BINARY_EXT => {
+                let len = self.u32()? as usize;
+                String::from_utf8(self.take(len)?.to_vec())?.into()
+            }
+            SMALL_BIG_EXT => {
+                let len = self.u8()? as usize;
+                self.big(len)?
+            }
+            LARGE_BIG_EXT => {
+                let len = self.u32()? as usize;
+                self.big(len)?
+            }
+            MAP_EXT => {
+                let arity = self.u32()? as usize;
+                let mut object = IObject::with_capacity(arity);
+
+                for _ in 0..arity {
+                    let key = self.term()?;
+                    let key = match key.destructure_ref() {
+                        DestructuredRef::String(key) => key.to_string(),
+                        DestructuredRef::Number(key) => number_to_string(key),
+                        _ => bail!("unsupported ETF map key {key:?}"),
+                    };
+                    object.insert(key, self.term()?);
+                }
+
+                object.into()
+            }
+            tag => bail!("unsupported ETF tag {tag}"),
+        };
+
+        Ok(value)
+    }
+
+    fn array(&mut self, len: usize) -> Result<IValue> {
+        let mut array = IArray::with_capacity(len.min(self.bytes.len()));
+        for _ in 0..len {
+            array.push(self.term()?);
+        }
+        Ok(array.into())
+    }
+
+    fn big(&mut self, len: usize) -> Result<IValue> {
+        let sign = self.u8()?;
+        let digits = self.take(len)?;
+        if len > 8 {
+            bail!("ETF big integer of {len} bytes does not fit in 64 bits");
+        }
+
+        let value = digits
+            .iter()
+            .rev()
+            .fold(0u64, |value, digit| (value << 8) | *digit as u64);
+
+        Ok(if sign == 0 {
+            value.to_string()
+        } else {
+            format!("-{value}")
+        }
+        .into())
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+fn atom(name: &str) -> IValue {
+    match name {
+        "nil" => IValue::NULL,
+        "true" => IValue::TRUE,
+        "false" => IValue::FALSE,
+        name => name.into(),
+    }
+}
+
+fn number_to_string(number: &INumber) -> String {
+    match (number.to_i64(), number.to_u64()) {
+        (Some(n), _) => n.to_string(),
+        (_, Some(n)) => n.to_string(),
+        _ => number.to_f64_lossy().to_string(),
+    }
+}
+
+fn write_term(out: &mut Vec<u8>, value: &IValue) -> Result<()> {
+    match value.destructure_ref() {
+        DestructuredRef::Null => write_atom(out, "nil"),
+        DestructuredRef::Bool(true) => write_atom(out, "true"),
+        DestructuredRef::Bool(false) => write_atom(out, "false"),
+        DestructuredRef::Number(number) => {
+            if number.has_decimal_point() {
+                out.push(NEW_FLOAT_EXT);
+                out.extend_from_slice(&number.to_f64_lossy().to_be_bytes());
+            } else if let Some(n) = number.to_i64() {
+                if let Ok(n) = u8::try_from(n) {
+                    out.extend_from_slice(&[SMALL_INTEGER_EXT, n]);
+                } else if let Ok(n) = i32::try_from(n) {
+                    out.push(INTEGER_EXT);
+                    out.extend_from_slice(&n.to_be_bytes());
+                } else {
+                    write_big(out, n < 0, n.unsigned_abs());
+                }
+            } else if let Some(n) = number.to_u64() {
+                write_big(out, false, n);
+            } else {
+                bail!("unsupported number {number:?}");
+            }
+        }
+        DestructuredRef::String(string) => write_binary(out, string.as_str()),
+        DestructuredRef::Array(array) => {
+            if !array.is_empty() {
+                out.push(LIST_EXT);
+                out.extend_from_slice(&u32::try_from(array.len())?.to_be_bytes());
+                for item in array {
+                    write_term(out, item)?;
+                }
+            }
+            out.push(NIL_EXT);
+        }
+        DestructuredRef::Object(object) => {
+            out.push(MAP_EXT);
+            out.extend_from_slice(&u32::try_from(object.len())?.to_be_bytes());
+            for (key, value) in object {
+                write_binary(out, key.as_str());
+                write_term(out, value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_atom(out: &mut Vec<u8>, name: &str) {
+    out.extend_from_slice(&[SMALL_ATOM_UTF8_EXT, name.len() as u8]);
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn write_binary(out: &mut Vec<u8>, string: &str) {
+    out.push(BINARY_EXT);
+    out.extend_from_slice(&(string.len() as u32).to_be_bytes());
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn write_big(out: &mut Vec<u8>, negative: bool, n: u64) {
+    let digits = n.to_le_bytes();
+    let len = 8 - n.leading_zeros() as usize / 8;
+
+    out.extend_from_slice(&[SMALL_BIG_EXT, len as u8, negative as u8]);
+    out.extend_from_slice(&digits[..len]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dataclasses::Presence,
+        gateway::{GatewayEvent, GuildCreate, RawGatewayEvent},
+    };
+
+    const READY: (&[u8], &str) = (
+        include_bytes!("../../tests/fixtures/etf/ready.etf"),
+        include_str!("../../tests/fixtures/etf/ready.json"),
+    );
+    const GUILD_CREATE: (&[u8], &str) = (
+        include_bytes!("../../tests/fixtures/etf/guild_create.etf"),
+        include_str!("../../tests/fixtures/etf/guild_create.json"),
+    );
+    const MESSAGE_CREATE: (&[u8], &str) = (
+        include_bytes!("../../tests/fixtures/etf/message_create.etf"),
+        include_str!("../../tests/fixtures/etf/message_create.json"),
+    );
+
+    fn event(value: &IValue) -> GatewayEvent {
+        ijson::from_value::<RawGatewayEvent>(value)
+            .unwrap()
+            .get_event_data()
+            .unwrap()
+    }
+
+    fn assert_same_event((etf, json): (&[u8], &str)) {
+        let etf = decode(etf).unwrap();
+        let json = serde_json::from_str::<IValue>(json).unwrap();
+
+        assert_eq!(format!("{:?}", event(&etf)), format!("{:?}", event(&json)));
+    }
+
+    #[test]
+    fn ready_matches_json() {
+        assert_same_event(READY);
+
+        let (etf, json) = READY;
+        assert_eq!(
+            decode(etf).unwrap(),
+            serde_json::from_str::<IValue>(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn guild_create_matches_json() {
+        assert_same_event(GUILD_CREATE);
+
+        let GatewayEvent::GuildCreate(GuildCreate::Available(guild)) =
+            event(&decode(GUILD_CREATE.0).unwrap())
+        else {
+            panic!("not a GUILD_CREATE");
+        };
+        let thread = &guild.threads.unwrap()[0];
+        assert_eq!(thread.guild_id, guild.id);
+        assert_eq!(thread.thread_metadata.auto_archive_duration, 4320);
+        assert_eq!(thread.member.as_ref().unwrap().flags, 1);
+    }
+
+    #[test]
+    fn message_create_matches_json() {
+        assert_same_event(MESSAGE_CREATE);
+
+        let (etf, json) = MESSAGE_CREATE;
+        assert_eq!(
+            decode(etf).unwrap(),
+            serde_json::from_str::<IValue>(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn big_integers() {
+        let value = decode(GUILD_CREATE.0).unwrap();
+        let presence = &value["d"]["presences"][0];
+        let activity = &presence["activities"][1];
+
+        assert_eq!(value["d"]["id"], IValue::from("1093210345113464883"));
+        assert_eq!(activity["created_at"], IValue::from("1713095901722"));
+
+        let presence = ijson::from_value::<Presence>(&ijson::ijson!({
+            "since": "1713095901722",
+            "activities": presence["activities"].clone(),
+            "status": "idle",
+            "afk": true,
+        }))
+        .unwrap();
+        assert_eq!(presence.since, Some(1713095901722));
+        assert_eq!(presence.activities[1].created_at, Some(1713095901722));
+        assert_eq!(presence.activities[1].flags, Some(1));
+        assert_eq!(
+            presence.activities[1].timestamps.as_ref().unwrap().start,
+            Some(1713095899000)
+        );
+
+        let mut negative = vec![VERSION, SMALL_BIG_EXT, 6, 1];
+        negative.extend_from_slice(&1713095901722u64.to_le_bytes()[..6]);
+        assert_eq!(decode(&negative).unwrap(), IValue::from("-1713095901722"));
+
+        let too_big = [VERSION, SMALL_BIG_EXT, 9, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert!(decode(&too_big).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for (etf, json) in [READY, GUILD_CREATE, MESSAGE_CREATE] {
+            let value = decode(etf).unwrap();
+            assert_eq!(decode(&encode(&value).unwrap()).unwrap(), value);
+
+            let value = serde_json::from_str::<IValue>(json).unwrap();
+            let decoded = decode(&encode(&value).unwrap()).unwrap();
+            assert_eq!(
+                format!("{:?}", event(&decoded)),
+                format!("{:?}", event(&value))
+            );
+        }
+
+        let value = ijson::ijson!({
+            "float": 1.5,
+            "negative": -42,
+            "wide": 4294967296u64,
+            "max": u64::MAX,
+            "tuple": [1, "two", null, true, false],
+            "empty": [],
+        });
+        let decoded = decode(&encode(&value).unwrap()).unwrap();
+        assert_eq!(decoded["float"], value["float"]);
+        assert_eq!(decoded["negative"], value["negative"]);
+        assert_eq!(decoded["wide"], IValue::from("4294967296"));
+        assert_eq!(decoded["max"], IValue::from(u64::MAX.to_string()));
+        assert_eq!(decoded["tuple"], value["tuple"]);
+        assert_eq!(decoded["empty"], value["empty"]);
+    }
+
+    #[test]
+    fn truncated() {
+        let (etf, _) = MESSAGE_CREATE;
+        assert!(decode(&etf[..etf.len() - 1]).is_err());
+        assert!(decode(&[VERSION]).is_err());
+        assert!(decode(&[130, NIL_EXT]).is_err());
+    }
+}
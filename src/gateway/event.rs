@@ -1,11 +1,12 @@
-use crate::dataclasses::{self, Snowflake};
+use crate::dataclasses::{self, Presence, Snowflake};
 
 use super::{
     event_data::{GatewayEvent, HelloData, ReadyData},
-    Intents, MessageCreateData,
+    GatewayError, GuildCreate, GuildMemberData, Intents, MessageCreateData, MessageUpdateData,
+    ThreadMemberUpdateData,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use ijson::{ijson, IValue};
 use serde::{Deserialize, Serialize};
 use tokio_tungstenite::tungstenite::Message;
@@ -22,17 +23,22 @@ pub struct RawGatewayEvent {
     pub t: Option<String>,
 }
 
-impl From<Message> for RawGatewayEvent {
-    fn from(value: Message) -> Self {
-        let t = match value {
-            Message::Text(event) => event,
-            Message::Frame(frame) => frame.to_string(),
-            _ => {
-                panic!("unknown message! {}", value);
-            }
+/// Decodes a JSON payload.
+impl TryFrom<Message> for RawGatewayEvent {
+    type Error = GatewayError;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        let decoded = match &value {
+            Message::Text(text) => serde_json::from_str::<Self>(text),
+            Message::Binary(bytes) => serde_json::from_slice::<Self>(bytes),
+            Message::Close(frame) => return Err(GatewayError::closed(frame.as_ref())),
+            _ => return Err(GatewayError::UnexpectedFrame(value)),
         };
 
-        serde_json::from_str::<Self>(t.as_str()).unwrap()
+        decoded.map_err(|source| GatewayError::Decode {
+            source: source.into(),
+            raw: value,
+        })
     }
 }
 
@@ -58,7 +64,7 @@ impl RawGatewayEvent {
         compress: Option<bool>,
         large_threshold: Option<u8>,
         shard: Option<(Snowflake, u64)>,
-        presence: Option<ijson::IValue>,
+        presence: Option<&Presence>,
         intents: Option<u64>,
     ) -> Self {
         let mut data = ijson!({
@@ -71,7 +77,7 @@ impl RawGatewayEvent {
         });
 
         if let Some((id, total)) = shard {
-            data["shard"] = ijson!([id, total]);
+            data["shard"] = ijson!([id.as_u64(), total]);
         }
 
         Self {
@@ -82,9 +88,49 @@ impl RawGatewayEvent {
         }
     }
 
+    /// Creates a new "Update Presence" structure. (op code: 3)
+    pub fn new_presence_update(presence: &Presence) -> Self {
+        Self {
+            op_code: 3,
+            data: Some(ijson!(presence)),
+            sequence: None,
+            t: None,
+        }
+    }
+
+    /// Creates a new "Heartbeat" structure, carrying the last sequence number received.
+    pub fn new_heartbeat(sequence: Option<u64>) -> Self {
+        Self {
+            op_code: 1,
+            data: sequence.map(|s| ijson!(s)),
+            sequence: None,
+            t: None,
+        }
+    }
+
+    /// Creates a new "Resume" structure. Used to replay missed events after a disconnect.
+    /// # Arguments
+    /// * `token` - The token of the bot.
+    /// * `session_id` - The session ID received in `READY`.
+    /// * `sequence` - The last sequence number received.
+    pub fn new_resume(token: &str, session_id: &str, sequence: Option<u64>) -> Self {
+        Self {
+            op_code: 6,
+            data: Some(ijson!({
+                "token": token,
+                "session_id": session_id,
+                "seq": sequence,
+            })),
+            sequence: None,
+            t: None,
+        }
+    }
+
+    /// Creates a new "Update Voice State" structure. (op code: 4)
+    /// A `channel_id` of `None` disconnects from voice.
     pub fn new_voice_state_update(
         guild_id: &Snowflake,
-        channel_id: &Snowflake,
+        channel_id: Option<Snowflake>,
         self_mute: bool,
         self_deaf: bool,
     ) -> Self {
@@ -101,38 +147,249 @@ impl RawGatewayEvent {
         }
     }
 
+    /// Creates a new "Request Guild Members" structure. (op code: 8)
+    /// # Arguments
+    /// * `guild_id` - The guild to get the members of.
+    /// * `query` - Which members to get.
+    /// * `limit` - Maximum number of members to send, `0` for every member matching `query`.
+    /// * `presences` - Whether to send the presences of the members.
+    /// * `nonce` - Sent back in every `GUILD_MEMBERS_CHUNK` of this request.
+    pub fn new_request_guild_members(
+        guild_id: &Snowflake,
+        query: &MemberQuery,
+        limit: u32,
+        presences: bool,
+        nonce: &str,
+    ) -> Self {
+        let mut data = ijson!({
+            "guild_id": guild_id,
+            "limit": limit,
+            "presences": presences,
+            "nonce": nonce,
+        });
+
+        match query {
+            MemberQuery::Query(query) => data["query"] = ijson!(query),
+            MemberQuery::UserIds(user_ids) => data["user_ids"] = ijson!(user_ids),
+        }
+
+        Self {
+            op_code: 8,
+            data: Some(data),
+            sequence: None,
+            t: None,
+        }
+    }
+
+    /// Decodes the data of the event.
+    ///
+    /// Dispatches that aren't modelled yet are returned as [`GatewayEvent::Unknown`].
+    ///
+    /// ```rust
+    /// use omu::{GatewayEvent, RawGatewayEvent};
+    ///
+    /// let event: RawGatewayEvent =
+    ///     serde_json::from_str(r#"{"op":0,"t":"SOMETHING_NEW","s":42,"d":{"a":1}}"#).unwrap();
+    ///
+    /// match event.get_event_data().unwrap() {
+    ///     GatewayEvent::Unknown { name, data } => {
+    ///         assert_eq!(name, "SOMETHING_NEW");
+    ///         assert_eq!(data["a"], 1.into());
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// assert!(event.get_event_data_strict().is_err());
+    /// ```
     pub fn get_event_data(&self) -> Result<GatewayEvent> {
+        self.decode_data()
+            .map_err(|err| match err.downcast::<serde_json::Error>() {
+                Ok(source) => GatewayError::Decode {
+                    source: source.into(),
+                    raw: Message::Text(serde_json::to_string(self).unwrap_or_default()),
+                }
+                .into(),
+                Err(err) => err,
+            })
+    }
+
+    fn decode_data(&self) -> Result<GatewayEvent> {
         if let Some(data) = &self.data {
             let e = match self.op_code {
-                0 => match self.t.as_deref().unwrap() {
+                0 => match self.t.as_deref().ok_or(GatewayError::MissingField {
+                    op_code: 0,
+                    field: "t",
+                })? {
                     "READY" => GatewayEvent::Ready(ijson::from_value::<ReadyData>(data)?),
+                    "RESUMED" => GatewayEvent::Resumed,
+                    "GUILD_CREATE" => GatewayEvent::GuildCreate(guild_create(data)?),
+                    "GUILD_UPDATE" => GatewayEvent::GuildUpdate(ijson::from_value(data)?),
+                    "GUILD_DELETE" => GatewayEvent::GuildDelete(ijson::from_value(data)?),
+                    "GUILD_MEMBER_ADD" => GatewayEvent::GuildMemberAdd(GuildMemberData {
+                        guild_id: ijson::from_value(&data["guild_id"])?,
+                        member: ijson::from_value(data)?,
+                    }),
+                    "GUILD_MEMBER_UPDATE" => GatewayEvent::GuildMemberUpdate(GuildMemberData {
+                        guild_id: ijson::from_value(&data["guild_id"])?,
+                        member: ijson::from_value(data)?,
+                    }),
+                    "GUILD_MEMBER_REMOVE" => {
+                        GatewayEvent::GuildMemberRemove(ijson::from_value(data)?)
+                    }
+                    "GUILD_MEMBERS_CHUNK" => {
+                        GatewayEvent::GuildMembersChunk(ijson::from_value(data)?)
+                    }
+                    "CHANNEL_CREATE" => GatewayEvent::ChannelCreate(ijson::from_value(data)?),
+                    "CHANNEL_UPDATE" => GatewayEvent::ChannelUpdate(ijson::from_value(data)?),
+                    "CHANNEL_DELETE" => GatewayEvent::ChannelDelete(ijson::from_value(data)?),
+                    "CHANNEL_PINS_UPDATE" => {
+                        GatewayEvent::ChannelPinsUpdate(ijson::from_value(data)?)
+                    }
+                    "THREAD_CREATE" => GatewayEvent::ThreadCreate(ijson::from_value(data)?),
+                    "THREAD_UPDATE" => GatewayEvent::ThreadUpdate(ijson::from_value(data)?),
+                    "THREAD_DELETE" => GatewayEvent::ThreadDelete(ijson::from_value(data)?),
+                    "THREAD_LIST_SYNC" => GatewayEvent::ThreadListSync(ijson::from_value(data)?),
+                    "THREAD_MEMBER_UPDATE" => {
+                        GatewayEvent::ThreadMemberUpdate(ThreadMemberUpdateData {
+                            guild_id: ijson::from_value(&data["guild_id"])?,
+                            member: ijson::from_value(data)?,
+                        })
+                    }
+                    "THREAD_MEMBERS_UPDATE" => {
+                        GatewayEvent::ThreadMembersUpdate(ijson::from_value(data)?)
+                    }
                     "MESSAGE_CREATE" => GatewayEvent::MessageCreate(MessageCreateData {
                         guild_id: data
                             .get("guild_id")
-                            .map(|v| v.as_string().unwrap().to_string()),
+                            .map(ijson::from_value::<Snowflake>)
+                            .transpose()?,
                         message: ijson::from_value::<dataclasses::Message>(data)?,
+                        member: data.get("member").map(ijson::from_value).transpose()?,
+                    }),
+                    "MESSAGE_UPDATE" => GatewayEvent::MessageUpdate(MessageUpdateData {
+                        guild_id: data
+                            .get("guild_id")
+                            .map(ijson::from_value::<Snowflake>)
+                            .transpose()?,
+                        message: ijson::from_value(data)?,
                     }),
-                    _ => return Err(anyhow!("unrecognized data type. raw: {:?}", self)),
+                    "MESSAGE_DELETE" => GatewayEvent::MessageDelete(ijson::from_value(data)?),
+                    "MESSAGE_DELETE_BULK" => {
+                        GatewayEvent::MessageDeleteBulk(ijson::from_value(data)?)
+                    }
+                    "PRESENCE_UPDATE" => GatewayEvent::PresenceUpdate(ijson::from_value(data)?),
+                    "TYPING_START" => GatewayEvent::TypingStart(ijson::from_value(data)?),
+                    "VOICE_STATE_UPDATE" => {
+                        GatewayEvent::VoiceStateUpdate(ijson::from_value(data)?)
+                    }
+                    "VOICE_SERVER_UPDATE" => {
+                        GatewayEvent::VoiceServerUpdate(ijson::from_value(data)?)
+                    }
+                    "USER_UPDATE" => GatewayEvent::UserUpdate(ijson::from_value(data)?),
+                    "MESSAGE_REACTION_ADD" => {
+                        GatewayEvent::MessageReactionAdd(ijson::from_value(data)?)
+                    }
+                    "MESSAGE_REACTION_REMOVE" => {
+                        GatewayEvent::MessageReactionRemove(ijson::from_value(data)?)
+                    }
+                    "MESSAGE_REACTION_REMOVE_ALL" => {
+                        GatewayEvent::MessageReactionRemoveAll(ijson::from_value(data)?)
+                    }
+                    "MESSAGE_REACTION_REMOVE_EMOJI" => {
+                        GatewayEvent::MessageReactionRemoveEmoji(ijson::from_value(data)?)
+                    }
+                    name => GatewayEvent::Unknown {
+                        name: name.to_string(),
+                        data: data.clone(),
+                    },
                 },
 
+                9 => GatewayEvent::InvalidSession {
+                    resumable: data.to_bool().unwrap_or(false),
+                },
                 10 => GatewayEvent::Hello(HelloData {
-                    heartbeat_interval: data["heartbeat_interval"]
-                        .as_number()
-                        .unwrap()
-                        .to_u64()
-                        .unwrap(),
+                    heartbeat_interval: data
+                        .get("heartbeat_interval")
+                        .and_then(|v| v.to_u64())
+                        .ok_or(GatewayError::MissingField {
+                            op_code: 10,
+                            field: "heartbeat_interval",
+                        })?,
                 }),
-                _ => return Err(anyhow!("unknown op code and data:\n{:?}", self)),
+                op_code => {
+                    return Err(GatewayError::UnexpectedOpCode {
+                        op_code,
+                        t: self.t.clone(),
+                    }
+                    .into())
+                }
             };
             Ok(e)
         } else {
             Ok(match self.op_code {
+                0 => GatewayEvent::Unknown {
+                    name: self.t.clone().unwrap_or_default(),
+                    data: IValue::NULL,
+                },
                 1 => GatewayEvent::Heartbeat,
+                7 => GatewayEvent::Reconnect,
+                9 => GatewayEvent::InvalidSession { resumable: false },
                 11 => GatewayEvent::HeartbeatAcknowledgement,
-                _ => return Err(anyhow!("unrecognized data type. raw: {:?}", self)),
+                op_code => {
+                    return Err(GatewayError::UnexpectedOpCode {
+                        op_code,
+                        t: self.t.clone(),
+                    }
+                    .into())
+                }
             })
         }
     }
+
+    /// Like [`RawGatewayEvent::get_event_data`], but fails on dispatches that aren't modelled,
+    /// with [`GatewayError::UnknownEvent`]. Useful in tests.
+    pub fn get_event_data_strict(&self) -> Result<GatewayEvent> {
+        match self.get_event_data()? {
+            GatewayEvent::Unknown { name, .. } => Err(GatewayError::UnknownEvent(name).into()),
+            event => Ok(event),
+        }
+    }
+}
+
+/// Decodes the guild of a `GUILD_CREATE`, whose threads may leave out the guild ID.
+fn guild_create(data: &IValue) -> Result<GuildCreate> {
+    if data.get("unavailable").and_then(|v| v.to_bool()) == Some(true) {
+        return Ok(GuildCreate::Unavailable(ijson::from_value(data)?));
+    }
+
+    let complete = data
+        .get("threads")
+        .and_then(|threads| threads.as_array())
+        .is_none_or(|threads| threads.iter().all(|t| t.get("guild_id").is_some()));
+    if complete {
+        return Ok(GuildCreate::Available(ijson::from_value(data)?));
+    }
+
+    let mut data = data.clone();
+    let guild_id = data["id"].clone();
+    if let Some(threads) = data.get_mut("threads").and_then(|t| t.as_array_mut()) {
+        for thread in threads.iter_mut().filter_map(|t| t.as_object_mut()) {
+            if thread.get("guild_id").is_none() {
+                thread.insert("guild_id", guild_id.clone());
+            }
+        }
+    }
+
+    Ok(GuildCreate::Available(ijson::from_value(&data)?))
+}
+
+/// Which members to get with "Request Guild Members".
+#[derive(Debug, Clone)]
+pub enum MemberQuery {
+    /// Members whose username starts with the query.
+    /// An empty string matches every member, which requires [`Intents::GUILD_MEMBERS`].
+    Query(String),
+    /// Members with these user IDs. (up to 100)
+    UserIds(Vec<Snowflake>),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -144,3 +401,83 @@ pub struct IdentifyConnectionProperty {
     /// The library name.
     pub device: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_snowflake_is_a_decode_error() {
+        let event = RawGatewayEvent::try_from(Message::Text(
+            r#"{"op":0,"s":2,"t":"MESSAGE_DELETE","d":{"id":"abc","channel_id":"1"}}"#.into(),
+        ))
+        .unwrap();
+
+        let err = event.get_event_data().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<GatewayError>(),
+            Some(GatewayError::Decode { .. })
+        ));
+    }
+
+    #[test]
+    fn unavailable_guild_create() {
+        let event = RawGatewayEvent::try_from(Message::Text(
+            r#"{"op":0,"s":1,"t":"GUILD_CREATE","d":{"id":"1","unavailable":true}}"#.into(),
+        ))
+        .unwrap();
+
+        let GatewayEvent::GuildCreate(GuildCreate::Unavailable(guild)) =
+            event.get_event_data().unwrap()
+        else {
+            panic!("not an unavailable GUILD_CREATE");
+        };
+        assert_eq!(guild.id, Snowflake::new(1));
+        assert!(guild.unavailable);
+    }
+
+    #[test]
+    fn reaction_burst_colors() {
+        let event = |data: &str| {
+            let payload = format!(r#"{{"op":0,"s":3,"t":"MESSAGE_REACTION_ADD","d":{data}}}"#);
+            match RawGatewayEvent::try_from(Message::Text(payload))
+                .unwrap()
+                .get_event_data()
+                .unwrap()
+            {
+                GatewayEvent::MessageReactionAdd(reaction) => reaction,
+                _ => panic!("not a MESSAGE_REACTION_ADD"),
+            }
+        };
+        let data =
+            r#""user_id":"1","channel_id":"2","message_id":"3","emoji":{"id":null,"name":"🔥"}"#;
+
+        let burst = event(&format!(
+            r##"{{{data},"burst":true,"burst_colors":["#ff0000","#00ff7f"],"type":1}}"##
+        ));
+        assert_eq!(burst.burst_colors, [0xff0000, 0x00ff7f]);
+
+        let normal = event(&format!(r#"{{{data},"burst":false,"type":0}}"#));
+        assert!(normal.burst_colors.is_empty());
+    }
+
+    #[test]
+    fn reply_has_referenced_message() {
+        let mut payload = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../../tests/fixtures/etf/message_create.json"
+        ))
+        .unwrap();
+        let mut referenced = payload["d"].clone();
+        referenced["id"] = "1229437851290140000".into();
+        referenced["referenced_message"] = serde_json::Value::Null;
+        payload["d"]["referenced_message"] = referenced;
+
+        let event = RawGatewayEvent::try_from(Message::Text(payload.to_string())).unwrap();
+        let GatewayEvent::MessageCreate(create) = event.get_event_data().unwrap() else {
+            panic!("not a MESSAGE_CREATE");
+        };
+        let referenced = create.message.referenced_message.unwrap();
+        assert_eq!(referenced.id, Snowflake::new(1229437851290140000));
+        assert!(referenced.referenced_message.is_none());
+    }
+}
@@ -1,15 +1,134 @@
-use crate::dataclasses::{Message, PartialGuild, User};
+use std::sync::Arc;
+
+use crate::{
+    dataclasses::{
+        Channel, ChannelType, Emoji, Guild, HexCode, HttpAttachable, Member, Message, PartialGuild,
+        PartialMessage, PresenceUpdate, Snowflake, Thread, ThreadMember, User, VoiceState,
+    },
+    http::client::HttpClient,
+};
+
+use anyhow::Result;
+
+use super::GatewayCloseCode;
 
 use ijson::IValue;
 use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum GatewayEvent {
     Ready(ReadyData),
+    /// Replay of missed events has finished after a resume.
+    Resumed,
     Hello(HelloData),
+    /// A guild became available: on startup, after an outage, or when the bot joined it.
+    /// Guilds still in an outage on startup come as [`GuildCreate::Unavailable`].
+    GuildCreate(GuildCreate),
+    GuildUpdate(Guild),
+    /// A guild became unavailable due to an outage (`unavailable`), or the bot was removed from it.
+    GuildDelete(PartialGuild),
+    /// A user joined a guild. Requires [`Intents::GUILD_MEMBERS`](super::Intents::GUILD_MEMBERS).
+    GuildMemberAdd(GuildMemberData),
+    /// Requires [`Intents::GUILD_MEMBERS`](super::Intents::GUILD_MEMBERS).
+    GuildMemberUpdate(GuildMemberData),
+    /// A user left or was removed from a guild.
+    /// Requires [`Intents::GUILD_MEMBERS`](super::Intents::GUILD_MEMBERS).
+    GuildMemberRemove(GuildMemberRemoveData),
+    /// A chunk of members, sent in response to "Request Guild Members".
+    GuildMembersChunk(GuildMembersChunkData),
+    /// A guild channel was created.
+    ChannelCreate(Channel<()>),
+    ChannelUpdate(Channel<()>),
+    ChannelDelete(Channel<()>),
+    /// A message was pinned or unpinned. Not sent when a pinned message is deleted.
+    ChannelPinsUpdate(ChannelPinsUpdateData),
+    /// A thread was created, or the bot was added to a private thread.
+    ThreadCreate(Thread),
+    ThreadUpdate(Thread),
+    ThreadDelete(ThreadDeleteData),
+    /// Active threads of channels the bot gained access to.
+    ThreadListSync(ThreadListSyncData),
+    /// The thread member of the bot was updated.
+    ThreadMemberUpdate(ThreadMemberUpdateData),
+    /// Users were added to or removed from a thread.
+    ThreadMembersUpdate(ThreadMembersUpdateData),
     MessageCreate(MessageCreateData),
+    /// A user's presence or info was updated.
+    /// Requires [`Intents::GUILD_PRESENCES`](super::Intents::GUILD_PRESENCES).
+    PresenceUpdate(PresenceUpdate),
+    /// A user started typing in a channel.
+    TypingStart(TypingStartData),
+    /// A user joined, left or moved between voice channels, or changed their voice settings.
+    VoiceStateUpdate(VoiceState),
+    /// The voice server of a guild was assigned or changed.
+    VoiceServerUpdate(VoiceServerUpdateData),
+    /// The bot's user was updated.
+    UserUpdate(User),
+    MessageUpdate(MessageUpdateData),
+    MessageDelete(MessageDeleteData),
+    MessageDeleteBulk(MessageDeleteBulkData),
+    MessageReactionAdd(MessageReactionAddData),
+    MessageReactionRemove(MessageReactionRemoveData),
+    /// Every reaction was removed from a message.
+    MessageReactionRemoveAll(MessageReactionRemoveAllData),
+    /// Every reaction of an emoji was removed from a message.
+    MessageReactionRemoveEmoji(MessageReactionRemoveEmojiData),
     HeartbeatAcknowledgement,
     Heartbeat,
+    /// Discord asked to reconnect and resume. The gateway does so on its own.
+    Reconnect,
+    /// The session has been invalidated. The gateway reconnects on its own,
+    /// resuming if `resumable`, or identifying again otherwise.
+    InvalidSession {
+        resumable: bool,
+    },
+    /// A dispatch that isn't modelled yet, with its raw data.
+    Unknown {
+        name: String,
+        data: IValue,
+    },
+    /// The connection was closed. The gateway reconnects on its own.
+    Closed {
+        code: Option<GatewayCloseCode>,
+        reason: String,
+    },
+}
+
+impl HttpAttachable for GatewayEvent {
+    fn attach(&mut self, http: Arc<HttpClient>) {
+        match self {
+            Self::MessageCreate(mc) => mc.message.attach(http),
+            Self::MessageUpdate(mu) => mu.message.attach(http),
+            Self::MessageDelete(md) => md.attach(http),
+            Self::MessageDeleteBulk(mdb) => mdb.attach(http),
+            Self::ChannelCreate(channel)
+            | Self::ChannelUpdate(channel)
+            | Self::ChannelDelete(channel) => channel.attach(http),
+            Self::ThreadCreate(thread) | Self::ThreadUpdate(thread) => thread.attach(http),
+            Self::GuildCreate(GuildCreate::Available(guild)) => {
+                for thread in guild.threads.iter_mut().flatten() {
+                    thread.attach(http.clone());
+                }
+            }
+            Self::ThreadListSync(tls) => {
+                for thread in tls.threads.iter_mut() {
+                    thread.attach(http.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The guild of a `GUILD_CREATE`.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum GuildCreate {
+    Available(Guild),
+    /// A guild that is still unavailable due to an outage.
+    Unavailable(PartialGuild),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,10 +148,278 @@ pub struct HelloData {
     pub heartbeat_interval: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildMemberData {
+    pub guild_id: Snowflake,
+    pub member: Member,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildMemberRemoveData {
+    pub guild_id: Snowflake,
+    pub user: User,
+}
+
+/// One chunk of the members requested with "Request Guild Members".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GuildMembersChunkData {
+    pub guild_id: Snowflake,
+    pub members: Vec<Member>,
+    pub chunk_index: u32,
+    pub chunk_count: u32,
+
+    /// The requested user IDs that were not found.
+    pub not_found: Option<Vec<Snowflake>>,
+
+    /// Presences of the members, if requested.
+    pub presences: Option<Vec<PresenceUpdate>>,
+
+    pub nonce: Option<String>,
+}
+
+/// Every chunk of a "Request Guild Members", put together.
+#[derive(Debug, Default)]
+pub struct GuildMembers {
+    pub members: Vec<Member>,
+
+    /// The requested user IDs that were not found.
+    pub not_found: Vec<Snowflake>,
+
+    /// Presences of the members, if requested.
+    pub presences: Vec<PresenceUpdate>,
+}
+
+impl GuildMembers {
+    /// Adds a received chunk.
+    pub fn extend(&mut self, chunk: GuildMembersChunkData) {
+        self.members.extend(chunk.members);
+        self.not_found.extend(chunk.not_found.unwrap_or_default());
+        self.presences.extend(chunk.presences.unwrap_or_default());
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MessageCreateData {
-    pub guild_id: Option<String>,
+    pub guild_id: Option<Snowflake>,
     pub message: Message,
-    // pub member
+
+    /// The author as a guild member, without `user`. Only in guilds.
+    pub member: Option<Member>,
     // pub mentions
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChannelPinsUpdateData {
+    pub guild_id: Option<Snowflake>,
+    pub channel_id: Snowflake,
+
+    /// When the most recent pinned message was pinned. (ISO8601 timestamp)
+    pub last_pin_timestamp: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadDeleteData {
+    pub id: Snowflake,
+    pub guild_id: Snowflake,
+    pub parent_id: Option<Snowflake>,
+    #[serde(rename = "type")]
+    pub type_: ChannelType,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadListSyncData {
+    pub guild_id: Snowflake,
+
+    /// The parent channels being synced. The whole guild if `None`.
+    pub channel_ids: Option<Vec<Snowflake>>,
+
+    /// Every active thread in the channels.
+    pub threads: Vec<Thread>,
+
+    /// The thread members of the bot, for the threads it has joined.
+    pub members: Vec<ThreadMember>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadMemberUpdateData {
+    pub guild_id: Snowflake,
+    pub member: ThreadMember,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadMembersUpdateData {
+    /// The ID of the thread.
+    pub id: Snowflake,
+    pub guild_id: Snowflake,
+
+    /// An approximate count of members in the thread, stops counting at 50.
+    pub member_count: usize,
+    pub added_members: Option<Vec<ThreadMember>>,
+    pub removed_member_ids: Option<Vec<Snowflake>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceServerUpdateData {
+    /// The voice connection token.
+    pub token: String,
+    pub guild_id: Snowflake,
+
+    /// The voice server host, `None` if it went away until a new one is allocated.
+    pub endpoint: Option<String>,
+}
+
+/// Everything needed to open a voice connection, from a voice state and a voice server update.
+#[derive(Debug, Clone)]
+pub struct VoiceConnectionInfo {
+    pub guild_id: Snowflake,
+    pub channel_id: Option<Snowflake>,
+    pub user_id: Snowflake,
+    pub session_id: String,
+    pub endpoint: String,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TypingStartData {
+    pub channel_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+    pub user_id: Snowflake,
+
+    /// When the user started typing. (Unix time in seconds)
+    pub timestamp: u64,
+
+    /// The member who started typing, if in a guild.
+    pub member: Option<Member>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageUpdateData {
+    pub guild_id: Option<Snowflake>,
+    pub message: PartialMessage,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDeleteData {
+    #[serde(skip)]
+    http: Option<Arc<HttpClient>>,
+
+    pub id: Snowflake,
+    pub channel_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+}
+
+impl MessageDeleteData {
+    /// Fetches the channel the message was deleted from.
+    pub async fn fetch_channel<T>(&self) -> Result<Channel<T>> {
+        fetch_channel(self.http.as_ref(), &self.channel_id).await
+    }
+}
+
+impl HttpAttachable for MessageDeleteData {
+    fn attach(&mut self, http: Arc<HttpClient>) {
+        self.http = Some(http);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageDeleteBulkData {
+    #[serde(skip)]
+    http: Option<Arc<HttpClient>>,
+
+    pub ids: Vec<Snowflake>,
+    pub channel_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+}
+
+impl MessageDeleteBulkData {
+    /// Fetches the channel the messages were deleted from.
+    pub async fn fetch_channel<T>(&self) -> Result<Channel<T>> {
+        fetch_channel(self.http.as_ref(), &self.channel_id).await
+    }
+}
+
+impl HttpAttachable for MessageDeleteBulkData {
+    fn attach(&mut self, http: Arc<HttpClient>) {
+        self.http = Some(http);
+    }
+}
+
+async fn fetch_channel<T>(
+    http: Option<&Arc<HttpClient>>,
+    channel_id: &Snowflake,
+) -> Result<Channel<T>> {
+    let http = http.ok_or_else(|| anyhow::anyhow!("no client was attached"))?;
+    let mut channel = http.get_channel(channel_id).await?;
+    channel.attach(http.clone());
+
+    Ok(channel)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReactionAddData {
+    pub user_id: Snowflake,
+    pub channel_id: Snowflake,
+    pub message_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+
+    /// The member who reacted, if in a guild.
+    pub member: Option<Member>,
+
+    /// A partial emoji: only `id`, `name` and `animated`.
+    pub emoji: Emoji,
+
+    /// The ID of the user who authored the message.
+    pub message_author_id: Option<Snowflake>,
+
+    /// Whether this is a super reaction.
+    pub burst: bool,
+
+    /// HEX colors used for the super reaction, sent as "#ff0000".
+    #[serde(default, with = "crate::dataclasses::hex_colors")]
+    pub burst_colors: Vec<HexCode>,
+
+    #[serde(rename = "type")]
+    pub type_: ReactionType,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReactionRemoveData {
+    pub user_id: Snowflake,
+    pub channel_id: Snowflake,
+    pub message_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+
+    /// A partial emoji: only `id`, `name` and `animated`.
+    pub emoji: Emoji,
+
+    /// Whether this is a super reaction.
+    pub burst: bool,
+
+    #[serde(rename = "type")]
+    pub type_: ReactionType,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReactionRemoveAllData {
+    pub channel_id: Snowflake,
+    pub message_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReactionRemoveEmojiData {
+    pub channel_id: Snowflake,
+    pub guild_id: Option<Snowflake>,
+    pub message_id: Snowflake,
+
+    /// A partial emoji: only `id` and `name`.
+    pub emoji: Emoji,
+}
+
+#[derive(Debug, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum ReactionType {
+    Normal = 0,
+    /// A super reaction.
+    Burst = 1,
+}
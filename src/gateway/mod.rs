@@ -1,11 +1,26 @@
+pub mod close_code;
+pub mod compression;
 pub mod core;
+pub mod encoding;
+pub mod error;
+pub mod etf;
 pub mod event;
 pub mod event_data;
 pub mod intents;
+pub mod ratelimit;
+pub mod reconnect;
 pub mod sharding;
+pub mod url;
 
+pub use close_code::*;
+pub use compression::*;
 pub use core::*;
+pub use encoding::*;
+pub use error::*;
 pub use event::*;
 pub use event_data::*;
 pub use intents::*;
+pub use ratelimit::*;
+pub use reconnect::*;
 pub use sharding::*;
+pub use url::*;
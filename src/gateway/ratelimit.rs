@@ -0,0 +1,171 @@
+use std::{collections::VecDeque, time::Duration};
+
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Instant},
+};
+
+/// Limits the commands sent over a gateway connection, which may not exceed
+/// 120 every 60 seconds without being closed with `4008`.
+///
+/// Every command takes a token, which comes back `period` later. Heartbeats may use the
+/// last `reserved` tokens, so that they are never held back by other commands.
+#[derive(Debug)]
+pub struct CommandRateLimiter {
+    pub limit: u32,
+    pub period: Duration,
+    pub reserved: u32,
+
+    /// When the tokens in use were taken, oldest first.
+    taken: VecDeque<Instant>,
+}
+
+impl CommandRateLimiter {
+    pub fn new(limit: u32, period: Duration, reserved: u32) -> Self {
+        Self {
+            limit,
+            period,
+            reserved,
+            taken: VecDeque::new(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        while self
+            .taken
+            .front()
+            .is_some_and(|taken| *taken + self.period <= now)
+        {
+            self.taken.pop_front();
+        }
+    }
+
+    /// The number of tokens left for commands other than heartbeats.
+    pub fn available(&mut self) -> u32 {
+        self.refill(Instant::now());
+        self.limit
+            .saturating_sub(self.reserved)
+            .saturating_sub(self.taken.len() as u32)
+    }
+
+    /// Takes a token for a command, or returns how long until one comes back.
+    pub fn try_acquire(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.refill(now);
+
+        let in_use = self.taken.len() as u32;
+        if in_use + self.reserved < self.limit {
+            self.taken.push_back(now);
+            return Ok(());
+        }
+
+        // the oldest tokens come back first
+        let index = (in_use + self.reserved - self.limit) as usize;
+        let back = self
+            .taken
+            .get(index)
+            .map_or(now, |taken| *taken + self.period);
+        Err(back.saturating_duration_since(now))
+    }
+
+    /// Takes a token for a heartbeat, which may be a reserved one.
+    pub fn acquire_reserved(&mut self) {
+        let now = Instant::now();
+        self.refill(now);
+
+        if (self.taken.len() as u32) < self.limit {
+            self.taken.push_back(now);
+        }
+    }
+
+    /// Gives back every token, for a new connection.
+    pub fn reset(&mut self) {
+        self.taken.clear();
+    }
+}
+
+impl Default for CommandRateLimiter {
+    /// 120 commands every 60 seconds, 3 of them kept for heartbeats.
+    fn default() -> Self {
+        Self::new(120, Duration::from_secs(60), 3)
+    }
+}
+
+/// Limits identifies, which are allowed once every 5 seconds per bucket.
+/// The bucket of a shard is `shard_id % max_concurrency`.
+///
+/// Shared by every shard of a bot, so that identifies sent when reconnecting are limited too.
+#[derive(Debug)]
+pub struct IdentifyLimiter {
+    pub max_concurrency: u64,
+    pub period: Duration,
+
+    /// When each bucket last identified.
+    buckets: Vec<Mutex<Option<Instant>>>,
+}
+
+impl IdentifyLimiter {
+    pub fn new(max_concurrency: u64) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+
+        Self {
+            max_concurrency,
+            period: Duration::from_secs(5),
+            buckets: (0..max_concurrency).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    /// Waits until the bucket of `shard_id` may identify, and takes its turn.
+    pub async fn acquire(&self, shard_id: u64) {
+        // waiters are queued on the lock, so a bucket identifies in order
+        let bucket = shard_id % self.buckets.len() as u64;
+        let mut last = self.buckets[bucket as usize].lock().await;
+
+        if let Some(last) = *last {
+            sleep_until(last + self.period).await;
+        }
+        *last = Some(Instant::now());
+    }
+}
+
+impl Default for IdentifyLimiter {
+    /// A single bucket, as for bots with a `max_concurrency` of 1.
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::sleep;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn reserves_heartbeats() {
+        let mut limiter = CommandRateLimiter::default();
+        for _ in 0..117 {
+            assert_eq!(limiter.try_acquire(), Ok(()));
+        }
+        assert_eq!(limiter.available(), 0);
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_secs(60)));
+
+        // heartbeats take the reserved tokens, and hold back other commands further
+        sleep(Duration::from_secs(30)).await;
+        for _ in 0..3 {
+            limiter.acquire_reserved();
+        }
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_secs(30)));
+
+        // the first tokens come back after the period
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(limiter.available(), 114);
+        assert_eq!(limiter.try_acquire(), Ok(()));
+
+        sleep(Duration::from_secs(30)).await;
+        assert_eq!(limiter.available(), 116);
+
+        limiter.reset();
+        assert_eq!(limiter.available(), 117);
+    }
+}
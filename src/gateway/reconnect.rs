@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+/// How the gateway retries after the connection drops.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+
+    /// The upper bound of the delay between retries.
+    pub max_delay: Duration,
+
+    /// Gives up after this many consecutive failed attempts. `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            max_attempts: Some(10),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// The delay before retrying after `attempt` failures.
+    /// Exponential backoff, with the upper half jittered.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        delay / 2 + (delay / 2).mul_f64(rand::random::<f64>())
+    }
+}
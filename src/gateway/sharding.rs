@@ -11,11 +11,12 @@ use crate::dataclasses::Snowflake;
 ///
 /// # Example
 /// ```rust
-/// use omu::gateway::sharding::get_sharding;
+/// use omu::{dataclasses::Snowflake, gateway::sharding::get_sharding};
 ///
-/// let (shard_id, num_shards) = get_sharding(1, 2);
-/// assert_eq!(shard_id, 1);
+/// let (shard_id, num_shards) = get_sharding(Snowflake::new(1 << 22), 2);
+/// assert_eq!(*shard_id, 1);
 /// assert_eq!(num_shards, 2);
+/// ```
 pub fn get_sharding(guild_id: Snowflake, num_shards: u64) -> (Snowflake, u64) {
     (Snowflake::new((*guild_id >> 22) % num_shards), num_shards)
 }
@@ -0,0 +1,85 @@
+use std::fmt::Display;
+
+use super::{GatewayEncoding, TransportCompression};
+
+/// Reads a query parameter of a URL.
+pub(crate) fn query_param<'a>(url: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| match pair.split_once('=') {
+            Some((k, value)) if k == key => Some(value),
+            _ => None,
+        })
+}
+
+/// Builds the URL to connect to the gateway with.
+///
+/// ```rust
+/// use omu::gateway::{GatewayUrl, TransportCompression};
+///
+/// use omu::gateway::GatewayEncoding;
+///
+/// let url = GatewayUrl::default().with_compression(TransportCompression::ZlibStream);
+/// assert_eq!(
+///     url.to_string(),
+///     "wss://gateway.discord.gg/?v=10&encoding=json&compress=zlib-stream"
+/// );
+///
+/// let url = GatewayUrl::default().with_encoding(GatewayEncoding::Etf);
+/// assert_eq!(url.to_string(), "wss://gateway.discord.gg/?v=10&encoding=etf");
+/// ```
+#[derive(Debug, Clone)]
+pub struct GatewayUrl {
+    pub base: String,
+    pub version: u8,
+    pub encoding: GatewayEncoding,
+    pub compression: Option<TransportCompression>,
+}
+
+impl Default for GatewayUrl {
+    fn default() -> Self {
+        Self::new("wss://gateway.discord.gg")
+    }
+}
+
+impl GatewayUrl {
+    pub fn new<K: ToString>(base: K) -> Self {
+        Self {
+            base: base.to_string(),
+            version: 10,
+            encoding: GatewayEncoding::Json,
+            compression: None,
+        }
+    }
+
+    /// Sets the payload encoding.
+    pub fn with_encoding(mut self, encoding: GatewayEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Enables transport compression.
+    pub fn with_compression(mut self, compression: TransportCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+}
+
+impl Display for GatewayUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/?v={}&encoding={}",
+            self.base.trim_end_matches('/'),
+            self.version,
+            self.encoding.as_str()
+        )?;
+
+        if let Some(compression) = self.compression {
+            write!(f, "&compress={}", compression.as_str())?;
+        }
+
+        Ok(())
+    }
+}
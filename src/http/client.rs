@@ -6,7 +6,7 @@ use tokio::sync::Mutex;
 
 use crate::dataclasses::{self, Channel, Snowflake};
 
-use super::http_messages::CreateMessage;
+use super::http_messages::{CreateMessage, GatewayBot};
 
 #[derive(Debug)]
 pub struct HttpClient {
@@ -74,10 +74,26 @@ impl HttpClient {
         Ok(res.json::<dataclasses::Message>().await?)
     }
 
+    /// Gets the gateway URL, the recommended number of shards and the session start limit.
+    pub async fn get_gateway_bot(&self) -> Result<GatewayBot> {
+        let client = self.client.lock().await;
+        let res = client
+            .get(format!("{}/gateway/bot", self.base))
+            .send()
+            .await?;
+
+        if res.status().as_u16() == 429 {
+            let json = res.json::<ijson::IValue>().await?;
+            return Err(HttpError::from(&json).into());
+        }
+
+        Ok(res.json::<GatewayBot>().await?)
+    }
+
     pub async fn get_channel<T>(&self, channel_id: &Snowflake) -> Result<Channel<T>> {
         let client = self.client.lock().await;
         let res = client
-            .get(format!("{}/channels/{}", self.base, channel_id.to_string()))
+            .get(format!("{}/channels/{}", self.base, channel_id))
             .send()
             .await?;
 
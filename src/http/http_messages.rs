@@ -1,12 +1,33 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::dataclasses::{AllowedMention, Embed, Message, MessageReference, Nounce, Snowflake};
 
 use super::client::HttpClient;
 
+/// Response of `GET /gateway/bot`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GatewayBot {
+    /// The WSS URL to connect to.
+    pub url: String,
+    /// The recommended number of shards.
+    pub shards: u64,
+    pub session_start_limit: SessionStartLimit,
+}
+
+/// How many sessions may still be started.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionStartLimit {
+    pub total: u64,
+    pub remaining: u64,
+    /// Milliseconds until `remaining` resets.
+    pub reset_after: u64,
+    /// How many shards may identify every 5 seconds.
+    pub max_concurrency: u64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CreateMessage {
     pub content: Option<String>,
@@ -72,7 +93,7 @@ impl<'a> PrepareCreateMessageBuilder<'a> {
     /// Fire the message.
     pub async fn send(&self) -> Result<Message> {
         self.http
-            .create_message(&self.channel_id.to_string().as_str(), &self.cm)
+            .create_message(self.channel_id.to_string().as_str(), &self.cm)
             .await
     }
 }
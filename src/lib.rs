@@ -12,4 +12,6 @@ pub mod dataclasses;
 
 pub mod cache;
 
+pub mod voice;
+
 pub mod utils;
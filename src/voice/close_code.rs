@@ -0,0 +1,90 @@
+/// Close codes sent by the voice gateway when it closes the connection.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceCloseCode {
+    #[error("unknown opcode (4001)")]
+    UnknownOpcode,
+    #[error("failed to decode payload (4002)")]
+    DecodeError,
+    #[error("not authenticated (4003)")]
+    NotAuthenticated,
+    #[error("authentication failed (4004)")]
+    AuthenticationFailed,
+    #[error("already authenticated (4005)")]
+    AlreadyAuthenticated,
+    #[error("session no longer valid (4006)")]
+    SessionInvalid,
+    #[error("session timed out (4009)")]
+    SessionTimedOut,
+    #[error("server not found (4011)")]
+    ServerNotFound,
+    #[error("unknown protocol (4012)")]
+    UnknownProtocol,
+    /// The bot was disconnected from the channel: kicked, moved, or the channel was deleted.
+    #[error("disconnected (4014)")]
+    Disconnected,
+    #[error("voice server crashed (4015)")]
+    ServerCrashed,
+    #[error("unknown encryption mode (4016)")]
+    UnknownEncryptionMode,
+    #[error("end-to-end encryption required (4017)")]
+    E2eeRequired,
+    #[error("bad request (4020)")]
+    BadRequest,
+    #[error("rate limited (4021)")]
+    RateLimited,
+    /// The call was terminated, e.g. the channel was deleted.
+    #[error("call terminated (4022)")]
+    CallTerminated,
+
+    /// Any other close code, including the standard websocket ones.
+    #[error("connection closed ({0})")]
+    Other(u16),
+}
+
+impl From<u16> for VoiceCloseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            4001 => Self::UnknownOpcode,
+            4002 => Self::DecodeError,
+            4003 => Self::NotAuthenticated,
+            4004 => Self::AuthenticationFailed,
+            4005 => Self::AlreadyAuthenticated,
+            4006 => Self::SessionInvalid,
+            4009 => Self::SessionTimedOut,
+            4011 => Self::ServerNotFound,
+            4012 => Self::UnknownProtocol,
+            4014 => Self::Disconnected,
+            4015 => Self::ServerCrashed,
+            4016 => Self::UnknownEncryptionMode,
+            4017 => Self::E2eeRequired,
+            4020 => Self::BadRequest,
+            4021 => Self::RateLimited,
+            4022 => Self::CallTerminated,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<VoiceCloseCode> for u16 {
+    fn from(value: VoiceCloseCode) -> u16 {
+        match value {
+            VoiceCloseCode::UnknownOpcode => 4001,
+            VoiceCloseCode::DecodeError => 4002,
+            VoiceCloseCode::NotAuthenticated => 4003,
+            VoiceCloseCode::AuthenticationFailed => 4004,
+            VoiceCloseCode::AlreadyAuthenticated => 4005,
+            VoiceCloseCode::SessionInvalid => 4006,
+            VoiceCloseCode::SessionTimedOut => 4009,
+            VoiceCloseCode::ServerNotFound => 4011,
+            VoiceCloseCode::UnknownProtocol => 4012,
+            VoiceCloseCode::Disconnected => 4014,
+            VoiceCloseCode::ServerCrashed => 4015,
+            VoiceCloseCode::UnknownEncryptionMode => 4016,
+            VoiceCloseCode::E2eeRequired => 4017,
+            VoiceCloseCode::BadRequest => 4020,
+            VoiceCloseCode::RateLimited => 4021,
+            VoiceCloseCode::CallTerminated => 4022,
+            VoiceCloseCode::Other(code) => code,
+        }
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use futures_util::{
+    stream::{SplitSink, SplitStream, StreamExt},
+    SinkExt,
+};
+use rand::Rng;
+use tokio::{
+    net::{TcpStream, UdpSocket},
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        Mutex,
+    },
+    time::{interval_at, timeout},
+};
+use tokio_tungstenite::{
+    tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    dataclasses::Snowflake,
+    gateway::{HeartbeatState, VoiceConnectionInfo},
+};
+
+use super::{
+    discover_ip, ClientDisconnectData, ClientsConnectData, EncryptionMode, JitterBuffer,
+    RawVoiceEvent, RtpHeader, RtpPacket, SessionDescriptionData, SpeakingData, SpeakingFlags,
+    VoiceCipher, VoiceError, VoiceHelloData, VoicePacket, VoiceReadyData, FRAME_SAMPLES,
+    SILENCE_FRAME, SILENCE_FRAMES,
+};
+
+pub type VoiceTx = UnboundedSender<Result<RawVoiceEvent>>;
+pub type VoiceRx = UnboundedReceiver<Result<RawVoiceEvent>>;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+type WsSource = SplitStream<WsStream>;
+
+/// The users sending audio, by SSRC.
+type Ssrcs = Arc<Mutex<HashMap<u32, Snowflake>>>;
+
+/// The users in the channel whose SSRC isn't known yet.
+type Unmapped = Arc<Mutex<HashSet<Snowflake>>>;
+
+/// The receivers of the audio of each user.
+type Listeners = Arc<Mutex<HashMap<Snowflake, UnboundedSender<VoicePacket>>>>;
+
+/// How long connecting may take, up to the session description.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Larger than any voice packet.
+pub(crate) const MAX_PACKET_SIZE: usize = 1500;
+
+/// What the handshake leaves to transmit with.
+struct Handshake {
+    heartbeat_interval: Duration,
+    ready: VoiceReadyData,
+    socket: UdpSocket,
+    mode: EncryptionMode,
+    secret_key: Vec<u8>,
+    last_sequence: Option<u64>,
+}
+
+/// The state of outgoing RTP packets.
+struct Transmitter {
+    sequence: u16,
+    timestamp: u32,
+    nonce: u32,
+    speaking: bool,
+}
+
+/// A connection to a voice server, sending and receiving Opus audio in a voice channel.
+///
+/// The voice websocket is read and written on two separate tasks, which also keep up the
+/// heartbeat. Audio is sent over UDP, encrypted with the negotiated [`EncryptionMode`].
+/// Received audio is decrypted and reordered on a third task, see [`VoiceConnection::listen`].
+///
+/// # Example
+/// ```rust,no_run
+/// use omu::voice::VoiceConnection;
+/// # use omu::{dataclasses::Snowflake, Client};
+/// # async fn example(
+/// #     client: Client,
+/// #     guild_id: Snowflake,
+/// #     channel_id: Snowflake,
+/// #     opus_frames: Vec<Vec<u8>>,
+/// # ) -> anyhow::Result<()> {
+///
+/// let info = client
+///     .update_voice_and_wait(&guild_id, &channel_id, false, true)
+///     .await?;
+/// let voice = VoiceConnection::connect(info).await?;
+///
+/// let mut interval = tokio::time::interval(std::time::Duration::from_millis(20));
+/// for frame in opus_frames {
+///     interval.tick().await;
+///     voice.send_opus(&frame).await?;
+/// }
+/// voice.stop_speaking().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct VoiceConnection {
+    pub info: VoiceConnectionInfo,
+
+    /// The synchronization source of the audio sent by the bot.
+    pub ssrc: u32,
+    pub mode: EncryptionMode,
+    pub heartbeat: Arc<Mutex<HeartbeatState>>,
+
+    /// The users sending audio, by SSRC. Filled by "Speaking" events, and by the first packet
+    /// of a new SSRC while a single connected user has none.
+    pub ssrcs: Ssrcs,
+
+    socket: Arc<UdpSocket>,
+    cipher: Arc<VoiceCipher>,
+    transmitter: Mutex<Transmitter>,
+    listeners: Listeners,
+    messages: UnboundedSender<Message>,
+    rx: Mutex<VoiceRx>,
+    cancel: CancellationToken,
+}
+
+impl VoiceConnection {
+    /// Connects to the voice server of `info`, as received through
+    /// [`Gateway::update_voice_and_wait`](crate::gateway::Gateway::update_voice_and_wait).
+    pub async fn connect(info: VoiceConnectionInfo) -> Result<Self> {
+        let url = format!("wss://{}/?v=8", info.endpoint);
+        Self::connect_to(&url, info).await
+    }
+
+    /// Connects to the voice websocket at `url` instead of the endpoint of `info`.
+    /// Useful to test against a local voice server.
+    pub async fn connect_to(url: &str, info: VoiceConnectionInfo) -> Result<Self> {
+        let (mut stream, _) = tokio_tungstenite::connect_async(url).await?;
+
+        let handshake = timeout(HANDSHAKE_TIMEOUT, Self::handshake(&mut stream, &info))
+            .await
+            .map_err(|_| anyhow::anyhow!("Timed out connecting to voice"))??;
+
+        let (sink, source) = stream.split();
+        let heartbeat = Arc::new(Mutex::new(HeartbeatState::new(
+            handshake.heartbeat_interval,
+        )));
+        let last_sequence = Arc::new(Mutex::new(handshake.last_sequence));
+        let socket = Arc::new(handshake.socket);
+        let cipher = Arc::new(VoiceCipher::new(handshake.mode, &handshake.secret_key)?);
+        let ssrcs = Ssrcs::default();
+        let unmapped = Unmapped::default();
+        let listeners = Listeners::default();
+        let cancel = CancellationToken::new();
+
+        let (messages, messages_rx) = mpsc::unbounded_channel::<Message>();
+        let (tx, rx) = mpsc::unbounded_channel::<Result<RawVoiceEvent>>();
+
+        tokio::spawn(Self::writer_task(
+            sink,
+            messages_rx,
+            heartbeat.clone(),
+            last_sequence.clone(),
+            cancel.clone(),
+            tx.clone(),
+        ));
+        tokio::spawn(Self::reader_task(
+            source,
+            heartbeat.clone(),
+            last_sequence,
+            ssrcs.clone(),
+            unmapped.clone(),
+            cancel.clone(),
+            tx,
+        ));
+        tokio::spawn(Self::receive_task(
+            socket.clone(),
+            cipher.clone(),
+            ssrcs.clone(),
+            unmapped,
+            listeners.clone(),
+            cancel.clone(),
+        ));
+
+        let mut rng = rand::thread_rng();
+
+        Ok(Self {
+            info,
+            ssrc: handshake.ready.ssrc,
+            mode: handshake.mode,
+            heartbeat,
+            ssrcs,
+            socket,
+            cipher,
+            transmitter: Mutex::new(Transmitter {
+                sequence: rng.gen(),
+                timestamp: rng.gen(),
+                nonce: 0,
+                speaking: false,
+            }),
+            listeners,
+            messages,
+            rx: Mutex::new(rx),
+            cancel,
+        })
+    }
+
+    /// Identifies, then on "Ready" discovers the external address over UDP and selects
+    /// the protocol, until the session description holds the key.
+    async fn handshake(stream: &mut WsStream, info: &VoiceConnectionInfo) -> Result<Handshake> {
+        stream
+            .send(RawVoiceEvent::new_identify(info).into())
+            .await?;
+
+        let mut heartbeat_interval = None;
+        let mut ready = None;
+        let mut last_sequence = None;
+
+        while let Some(message) = stream.next().await {
+            let event = RawVoiceEvent::try_from(message?)?;
+            if event.sequence.is_some() {
+                last_sequence = event.sequence;
+            }
+
+            match event.op_code {
+                2 => {
+                    let data = event.data::<VoiceReadyData>()?;
+                    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                    socket.connect((data.ip.as_str(), data.port)).await?;
+
+                    let (address, port) = discover_ip(&socket, data.ssrc).await?;
+                    let mode = EncryptionMode::negotiate(&data.modes)?;
+                    stream
+                        .send(RawVoiceEvent::new_select_protocol(&address, port, mode).into())
+                        .await?;
+
+                    ready = Some((data, socket, mode));
+                }
+                4 => {
+                    let data = event.data::<SessionDescriptionData>()?;
+                    let (Some(interval), Some((ready, socket, mode))) = (heartbeat_interval, ready)
+                    else {
+                        return Err(anyhow::anyhow!(
+                            "Session description received before \"Hello\" and \"Ready\""
+                        ));
+                    };
+
+                    if data.mode != mode.as_str() {
+                        return Err(VoiceError::UnsupportedModes(vec![data.mode]).into());
+                    }
+
+                    return Ok(Handshake {
+                        heartbeat_interval: interval,
+                        ready,
+                        socket,
+                        mode,
+                        secret_key: data.secret_key,
+                        last_sequence,
+                    });
+                }
+                8 => {
+                    let data = event.data::<VoiceHelloData>()?;
+                    heartbeat_interval =
+                        Some(Duration::from_secs_f64(data.heartbeat_interval / 1000.0));
+                }
+                _ => {}
+            }
+        }
+
+        Err(VoiceError::closed(None).into())
+    }
+
+    /// The round-trip time of the last acknowledged heartbeat.
+    pub async fn latency(&self) -> Option<Duration> {
+        self.heartbeat.lock().await.latency
+    }
+
+    /// Receives the audio of a user, in order. Replaces the previous receiver of that user.
+    ///
+    /// Audio is only received from users whose SSRC is known, from a "Speaking" event
+    /// sent before they start speaking, or from being the only user that connected without one.
+    /// Dropping the receiver stops receiving.
+    ///
+    /// ```rust,no_run
+    /// # use omu::{dataclasses::Snowflake, voice::VoiceConnection};
+    /// # async fn example(voice: VoiceConnection, user_id: Snowflake) {
+    /// let mut audio = voice.listen(user_id).await;
+    /// while let Some(packet) = audio.recv().await {
+    ///     println!("{} bytes of Opus at {}", packet.opus.len(), packet.timestamp);
+    /// }
+    /// # }
+    /// ```
+    pub async fn listen(&self, user_id: Snowflake) -> UnboundedReceiver<VoicePacket> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.listeners.lock().await.insert(user_id, sender);
+        receiver
+    }
+
+    /// Read one event at a time, other than heartbeat ACKs.
+    /// Returns `None` once the connection is closed.
+    pub async fn next(&self) -> Result<Option<RawVoiceEvent>> {
+        self.rx.lock().await.recv().await.transpose()
+    }
+
+    /// Sends an event on the voice websocket.
+    pub fn send_event(&self, event: RawVoiceEvent) -> Result<()> {
+        self.messages
+            .send(event.into())
+            .map_err(|_| anyhow::anyhow!("Already disconnected"))
+    }
+
+    /// Sets the speaking state. [`VoiceConnection::send_opus`] starts speaking on its own.
+    pub async fn set_speaking(&self, speaking: SpeakingFlags) -> Result<()> {
+        let mut transmitter = self.transmitter.lock().await;
+        self.send_event(RawVoiceEvent::new_speaking(speaking, self.ssrc))?;
+        transmitter.speaking = !speaking.is_empty();
+
+        Ok(())
+    }
+
+    /// Sends a 20 ms Opus frame (48 kHz, stereo), speaking first if needed.
+    /// Frames are sent as given, so they must be paced by the caller, one every 20 ms.
+    pub async fn send_opus(&self, frame: &[u8]) -> Result<()> {
+        let mut transmitter = self.transmitter.lock().await;
+        if !transmitter.speaking {
+            self.send_event(RawVoiceEvent::new_speaking(
+                SpeakingFlags::MICROPHONE,
+                self.ssrc,
+            ))?;
+            transmitter.speaking = true;
+        }
+
+        self.send_packet(&mut transmitter, frame).await
+    }
+
+    /// Sends frames of silence, then stops speaking. Does nothing if not speaking.
+    pub async fn stop_speaking(&self) -> Result<()> {
+        let mut transmitter = self.transmitter.lock().await;
+        if !transmitter.speaking {
+            return Ok(());
+        }
+
+        for _ in 0..SILENCE_FRAMES {
+            self.send_packet(&mut transmitter, &SILENCE_FRAME).await?;
+        }
+
+        self.send_event(RawVoiceEvent::new_speaking(
+            SpeakingFlags::empty(),
+            self.ssrc,
+        ))?;
+        transmitter.speaking = false;
+
+        Ok(())
+    }
+
+    async fn send_packet(&self, transmitter: &mut Transmitter, frame: &[u8]) -> Result<()> {
+        let header = RtpHeader {
+            sequence: transmitter.sequence,
+            timestamp: transmitter.timestamp,
+            ssrc: self.ssrc,
+        };
+        let packet = self
+            .cipher
+            .encrypt(&header.to_bytes(), frame, transmitter.nonce)?;
+        self.socket.send(&packet).await?;
+
+        transmitter.sequence = transmitter.sequence.wrapping_add(1);
+        transmitter.timestamp = transmitter.timestamp.wrapping_add(FRAME_SAMPLES);
+        transmitter.nonce = transmitter.nonce.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Closes the voice websocket. Leaving the channel is done through the main gateway,
+    /// with a `channel_id` of `None`.
+    pub async fn disconnect(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(anyhow::anyhow!("Already disconnected"));
+        }
+
+        self.messages
+            .send(Message::Close(Some(CloseFrame {
+                code: CloseCode::Normal,
+                reason: Cow::from("Disconnected"),
+            })))
+            .ok();
+
+        Ok(())
+    }
+
+    async fn writer_task(
+        mut sink: WsSink,
+        mut messages: UnboundedReceiver<Message>,
+        heartbeat: Arc<Mutex<HeartbeatState>>,
+        last_sequence: Arc<Mutex<Option<u64>>>,
+        cancel: CancellationToken,
+        tx: VoiceTx,
+    ) {
+        let period = heartbeat.lock().await.interval;
+        let mut interval = interval_at(tokio::time::Instant::now() + period, period);
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                message = messages.recv() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    let close = message.is_close();
+
+                    if sink.send(message).await.is_err() || close {
+                        cancel.cancel();
+                        break;
+                    }
+                }
+                _ = interval.tick() => {
+                    if heartbeat.lock().await.pending_since.take().is_some() {
+                        // no ACK since the last heartbeat, so the connection is zombied
+                        tx.send(Err(VoiceError::HeartbeatTimeout.into())).ok();
+                        sink.send(Message::Close(Some(CloseFrame {
+                            code: CloseCode::Restart,
+                            reason: Cow::from("Heartbeat not acknowledged"),
+                        })))
+                        .await
+                        .ok();
+                        cancel.cancel();
+                        break;
+                    }
+
+                    let nonce = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|time| time.as_millis() as u64)
+                        .unwrap_or_default();
+                    let sequence = *last_sequence.lock().await;
+
+                    heartbeat
+                        .lock()
+                        .await
+                        .pending_since
+                        .get_or_insert_with(Instant::now);
+
+                    let event = RawVoiceEvent::new_heartbeat(nonce, sequence);
+                    if sink.send(event.into()).await.is_err() {
+                        cancel.cancel();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn reader_task(
+        mut source: WsSource,
+        heartbeat: Arc<Mutex<HeartbeatState>>,
+        last_sequence: Arc<Mutex<Option<u64>>>,
+        ssrcs: Ssrcs,
+        unmapped: Unmapped,
+        cancel: CancellationToken,
+        tx: VoiceTx,
+    ) {
+        loop {
+            let message = tokio::select! {
+                _ = cancel.cancelled() => break,
+                message = source.next() => message,
+            };
+
+            let message = match message {
+                Some(Ok(message)) => message,
+                Some(Err(err)) => {
+                    tx.send(Err(err.into())).ok();
+                    break;
+                }
+                None => {
+                    tx.send(Err(VoiceError::closed(None).into())).ok();
+                    break;
+                }
+            };
+
+            match RawVoiceEvent::try_from(message) {
+                Ok(event) => {
+                    if event.sequence.is_some() {
+                        *last_sequence.lock().await = event.sequence;
+                    }
+
+                    match event.op_code {
+                        6 => {
+                            heartbeat.lock().await.acknowledge();
+                            continue;
+                        }
+                        5 => {
+                            if let Ok(data) = event.data::<SpeakingData>() {
+                                let mut ssrcs = ssrcs.lock().await;
+                                ssrcs.insert(data.ssrc, data.user_id);
+                                unmapped.lock().await.remove(&data.user_id);
+                            }
+                        }
+                        11 => {
+                            // only user IDs: their SSRCs are learned when they speak
+                            if let Ok(data) = event.data::<ClientsConnectData>() {
+                                let ssrcs = ssrcs.lock().await;
+                                unmapped.lock().await.extend(
+                                    data.user_ids
+                                        .into_iter()
+                                        .filter(|user_id| !ssrcs.values().any(|id| id == user_id)),
+                                );
+                            }
+                        }
+                        13 => {
+                            if let Ok(data) = event.data::<ClientDisconnectData>() {
+                                let mut ssrcs = ssrcs.lock().await;
+                                ssrcs.retain(|_, user_id| *user_id != data.user_id);
+                                unmapped.lock().await.remove(&data.user_id);
+                            }
+                        }
+                        _ => {}
+                    }
+
+                    tx.send(Ok(event)).ok();
+                }
+                Err(err @ VoiceError::Closed { .. }) => {
+                    tx.send(Err(err.into())).ok();
+                    break;
+                }
+                Err(err) => {
+                    tx.send(Err(err.into())).ok();
+                }
+            }
+        }
+
+        cancel.cancel();
+    }
+
+    /// Decrypts audio packets, and hands them out in order to the listener of their user.
+    async fn receive_task(
+        socket: Arc<UdpSocket>,
+        cipher: Arc<VoiceCipher>,
+        ssrcs: Ssrcs,
+        unmapped: Unmapped,
+        listeners: Listeners,
+        cancel: CancellationToken,
+    ) {
+        let mut buffers = HashMap::<u32, JitterBuffer>::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+
+        loop {
+            let size = tokio::select! {
+                _ = cancel.cancelled() => break,
+                size = socket.recv(&mut buffer) => match size {
+                    Ok(size) => size,
+                    Err(_) => continue,
+                },
+            };
+
+            // RTCP, or a packet of another payload type
+            let Some(packet) = RtpPacket::parse(&buffer[..size]) else {
+                continue;
+            };
+            let ssrc = packet.header.ssrc;
+            let Some(user_id) = Self::user_of(&ssrcs, &unmapped, ssrc).await else {
+                continue;
+            };
+
+            let mut listeners = listeners.lock().await;
+            let Some(listener) = listeners.get(&user_id) else {
+                buffers.remove(&ssrc);
+                continue;
+            };
+            if listener.is_closed() {
+                listeners.remove(&user_id);
+                continue;
+            }
+
+            let Ok(opus) = cipher.decrypt(&packet) else {
+                continue;
+            };
+            let ready = buffers.entry(ssrc).or_default().push(VoicePacket {
+                user_id,
+                ssrc,
+                sequence: packet.header.sequence,
+                timestamp: packet.header.timestamp,
+                opus,
+            });
+            for packet in ready {
+                listener.send(packet).ok();
+            }
+        }
+    }
+
+    /// The user sending `ssrc`. An unknown SSRC belongs to the only connected user without one.
+    async fn user_of(ssrcs: &Ssrcs, unmapped: &Unmapped, ssrc: u32) -> Option<Snowflake> {
+        let mut ssrcs = ssrcs.lock().await;
+        if let Some(user_id) = ssrcs.get(&ssrc) {
+            return Some(*user_id);
+        }
+
+        let mut unmapped = unmapped.lock().await;
+        if unmapped.len() != 1 {
+            return None;
+        }
+        let user_id = unmapped.drain().next()?;
+        ssrcs.insert(ssrc, user_id);
+        Some(user_id)
+    }
+}
+
+impl Drop for VoiceConnection {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aes_gcm::{
+        aead::{Aead, KeyInit, Payload},
+        Aes256Gcm,
+    };
+
+    use super::*;
+    use crate::voice::{
+        mock::{info, mock_server, KEY, SSRC},
+        NONCE_SIZE,
+    };
+
+    fn sequence(packet: &[u8]) -> u16 {
+        u16::from_be_bytes([packet[2], packet[3]])
+    }
+
+    fn timestamp(packet: &[u8]) -> u32 {
+        u32::from_be_bytes(packet[4..8].try_into().unwrap())
+    }
+
+    #[tokio::test]
+    async fn sends_encrypted_rtp() {
+        let (url, mut packets, _) = mock_server(30000.0, true, vec![]).await;
+        let voice = VoiceConnection::connect_to(&url, info()).await.unwrap();
+        assert_eq!(voice.ssrc, SSRC);
+        assert_eq!(voice.mode, EncryptionMode::Aes256Gcm);
+
+        voice.send_opus(&[1, 2, 3]).await.unwrap();
+        voice.send_opus(&[4, 5, 6]).await.unwrap();
+        let first = packets.recv().await.unwrap();
+        let second = packets.recv().await.unwrap();
+
+        // version 2, payload type 0x78 (Opus), then the sequence, timestamp and SSRC
+        assert_eq!(first[..2], [0x80, 0x78]);
+        assert_eq!(first[8..12], SSRC.to_be_bytes());
+        assert_eq!(sequence(&second), sequence(&first).wrapping_add(1));
+        assert_eq!(
+            timestamp(&second),
+            timestamp(&first).wrapping_add(FRAME_SAMPLES)
+        );
+
+        // the nonce is a counter, appended to the packet and padded with zeros
+        let suffix = first.len() - NONCE_SIZE;
+        assert_eq!(first[suffix..], 0u32.to_be_bytes());
+        assert_eq!(second[suffix..], 1u32.to_be_bytes());
+
+        let mut nonce = [0; 12];
+        nonce[..NONCE_SIZE].copy_from_slice(&first[suffix..]);
+        let payload = Payload {
+            msg: &first[12..suffix],
+            aad: &first[..12],
+        };
+        let opus = Aes256Gcm::new_from_slice(&KEY)
+            .unwrap()
+            .decrypt((&nonce).into(), payload)
+            .unwrap();
+        assert_eq!(opus, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn closes_on_missed_heartbeat_ack() {
+        let (url, _packets, _) = mock_server(50.0, false, vec![]).await;
+        let voice = VoiceConnection::connect_to(&url, info()).await.unwrap();
+
+        let err = timeout(Duration::from_secs(5), voice.next())
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VoiceError>(),
+            Some(VoiceError::HeartbeatTimeout)
+        ));
+        assert!(voice.cancel.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn maps_ssrc_of_connected_client() {
+        let user_id = Snowflake::new(1093210012486668399);
+        let (url, _packets, udp) = mock_server(30000.0, true, vec![user_id]).await;
+        let voice = VoiceConnection::connect_to(&url, info()).await.unwrap();
+        let mut audio = voice.listen(user_id).await;
+
+        let event = voice.next().await.unwrap().unwrap();
+        assert_eq!(event.op_code, 11);
+
+        // the user hasn't spoken, but is the only one whose SSRC is unknown
+        let header = RtpHeader {
+            sequence: 1,
+            timestamp: 960,
+            ssrc: 99,
+        };
+        let packet = VoiceCipher::new(EncryptionMode::Aes256Gcm, &KEY)
+            .unwrap()
+            .encrypt(&header.to_bytes(), &[1, 2, 3], 0)
+            .unwrap();
+        let port = voice.socket.local_addr().unwrap().port();
+        udp.send_to(&packet, ("127.0.0.1", port)).await.unwrap();
+
+        let received = timeout(Duration::from_secs(5), audio.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.user_id, user_id);
+        assert_eq!(received.ssrc, 99);
+        assert_eq!(received.opus, [1, 2, 3]);
+        assert_eq!(voice.ssrcs.lock().await.get(&99), Some(&user_id));
+    }
+}
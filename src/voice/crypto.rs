@@ -0,0 +1,171 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm,
+};
+use anyhow::Result;
+use chacha20poly1305::XChaCha20Poly1305;
+
+use super::{RtpPacket, VoiceError};
+
+/// The size of the nonce appended to each packet.
+pub const NONCE_SIZE: usize = 4;
+
+/// The AEAD encryption modes of RTP payloads. The RTP header is authenticated, not encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionMode {
+    /// `aead_aes256_gcm_rtpsize`, preferred when offered.
+    Aes256Gcm,
+    /// `aead_xchacha20_poly1305_rtpsize`, supported by every voice server.
+    XChaCha20Poly1305,
+}
+
+impl EncryptionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "aead_aes256_gcm_rtpsize",
+            Self::XChaCha20Poly1305 => "aead_xchacha20_poly1305_rtpsize",
+        }
+    }
+
+    /// The preferred mode out of the ones offered by the voice server.
+    pub fn negotiate(modes: &[String]) -> Result<Self, VoiceError> {
+        [Self::Aes256Gcm, Self::XChaCha20Poly1305]
+            .into_iter()
+            .find(|mode| modes.iter().any(|m| m == mode.as_str()))
+            .ok_or_else(|| VoiceError::UnsupportedModes(modes.to_vec()))
+    }
+}
+
+/// Encrypts and decrypts RTP payloads with the key of the session description.
+pub enum VoiceCipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    XChaCha20Poly1305(XChaCha20Poly1305),
+}
+
+impl VoiceCipher {
+    pub fn new(mode: EncryptionMode, secret_key: &[u8]) -> Result<Self> {
+        Ok(match mode {
+            EncryptionMode::Aes256Gcm => {
+                Self::Aes256Gcm(Box::new(Aes256Gcm::new_from_slice(secret_key)?))
+            }
+            EncryptionMode::XChaCha20Poly1305 => {
+                Self::XChaCha20Poly1305(XChaCha20Poly1305::new_from_slice(secret_key)?)
+            }
+        })
+    }
+
+    /// Builds a packet: `header`, then the encrypted `payload` with its tag, then `nonce`.
+    pub fn encrypt(
+        &self,
+        header: &[u8],
+        payload: &[u8],
+        nonce: u32,
+    ) -> Result<Vec<u8>, VoiceError> {
+        let payload = Payload {
+            msg: payload,
+            aad: header,
+        };
+
+        // the nonce is sent truncated to 4 bytes, and padded with zeroes to the cipher's size
+        let encrypted = match self {
+            Self::Aes256Gcm(cipher) => {
+                cipher.encrypt(Self::full_nonce::<12>(nonce).as_ref().into(), payload)
+            }
+            Self::XChaCha20Poly1305(cipher) => {
+                cipher.encrypt(Self::full_nonce::<24>(nonce).as_ref().into(), payload)
+            }
+        }
+        .map_err(|_| VoiceError::Crypto)?;
+
+        let mut packet = Vec::with_capacity(header.len() + encrypted.len() + NONCE_SIZE);
+        packet.extend_from_slice(header);
+        packet.extend_from_slice(&encrypted);
+        packet.extend_from_slice(&nonce.to_be_bytes());
+
+        Ok(packet)
+    }
+
+    /// Decrypts the payload of `packet`, without its extension and padding.
+    pub fn decrypt(&self, packet: &RtpPacket) -> Result<Vec<u8>, VoiceError> {
+        let payload = Payload {
+            msg: packet.encrypted,
+            aad: packet.aad,
+        };
+
+        let mut decrypted = match self {
+            Self::Aes256Gcm(cipher) => cipher.decrypt(
+                Self::full_nonce::<12>(packet.nonce).as_ref().into(),
+                payload,
+            ),
+            Self::XChaCha20Poly1305(cipher) => cipher.decrypt(
+                Self::full_nonce::<24>(packet.nonce).as_ref().into(),
+                payload,
+            ),
+        }
+        .map_err(|_| VoiceError::Crypto)?;
+
+        // the last byte of padding is its length
+        if packet.padding {
+            let padding = decrypted.last().copied().unwrap_or_default() as usize;
+            decrypted.truncate(decrypted.len().saturating_sub(padding));
+        }
+        if packet.extension_size > decrypted.len() {
+            return Err(VoiceError::Crypto);
+        }
+        decrypted.drain(..packet.extension_size);
+
+        Ok(decrypted)
+    }
+
+    fn full_nonce<const N: usize>(nonce: u32) -> [u8; N] {
+        let mut full = [0; N];
+        full[..NONCE_SIZE].copy_from_slice(&nonce.to_be_bytes());
+        full
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::RtpHeader;
+
+    #[test]
+    fn round_trip() {
+        let header = RtpHeader {
+            sequence: 1,
+            timestamp: 960,
+            ssrc: 42,
+        }
+        .to_bytes();
+
+        for mode in [EncryptionMode::Aes256Gcm, EncryptionMode::XChaCha20Poly1305] {
+            let cipher = VoiceCipher::new(mode, &[7; 32]).unwrap();
+            let mut packet = cipher.encrypt(&header, b"opus", u32::MAX).unwrap();
+            assert_eq!(&packet[..header.len()], header);
+            assert_eq!(&packet[packet.len() - NONCE_SIZE..], u32::MAX.to_be_bytes());
+
+            let parsed = RtpPacket::parse(&packet).unwrap();
+            assert_eq!(cipher.decrypt(&parsed).unwrap(), b"opus");
+
+            // the header is authenticated
+            packet[2] ^= 1;
+            let parsed = RtpPacket::parse(&packet).unwrap();
+            assert!(matches!(cipher.decrypt(&parsed), Err(VoiceError::Crypto)));
+        }
+    }
+
+    #[test]
+    fn negotiates_preferred_mode() {
+        let modes =
+            ["aead_xchacha20_poly1305_rtpsize", "aead_aes256_gcm_rtpsize"].map(String::from);
+        assert_eq!(
+            EncryptionMode::negotiate(&modes).unwrap(),
+            EncryptionMode::Aes256Gcm
+        );
+        assert_eq!(
+            EncryptionMode::negotiate(&modes[..1]).unwrap(),
+            EncryptionMode::XChaCha20Poly1305
+        );
+        assert!(EncryptionMode::negotiate(&["xsalsa20_poly1305".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message};
+
+use super::VoiceCloseCode;
+
+/// Errors of a voice connection.
+#[derive(thiserror::Error, Debug)]
+pub enum VoiceError {
+    /// The voice websocket was closed.
+    #[error("connection closed ({code:?}): {reason}")]
+    Closed {
+        code: Option<VoiceCloseCode>,
+        reason: String,
+    },
+
+    /// A payload could not be decoded.
+    #[error("failed to decode payload: {source}")]
+    Decode {
+        source: Box<dyn std::error::Error + Send + Sync>,
+        raw: Message,
+    },
+
+    /// A websocket frame that carries no payload, such as a ping.
+    #[error("unexpected frame: {0:?}")]
+    UnexpectedFrame(Message),
+
+    /// None of the encryption modes offered by the voice server are supported.
+    #[error("no supported encryption mode in {0:?}")]
+    UnsupportedModes(Vec<String>),
+
+    /// The voice server didn't answer IP discovery properly.
+    #[error("IP discovery failed: {0}")]
+    IpDiscovery(&'static str),
+
+    /// An RTP packet could not be encrypted or decrypted.
+    #[error("packet encryption failed")]
+    Crypto,
+
+    /// A heartbeat wasn't acknowledged before the next one, so the connection was closed.
+    #[error("heartbeat not acknowledged")]
+    HeartbeatTimeout,
+}
+
+impl VoiceError {
+    pub(crate) fn closed(frame: Option<&CloseFrame>) -> Self {
+        Self::Closed {
+            code: frame.map(|frame| u16::from(frame.code).into()),
+            reason: frame
+                .map(|frame| frame.reason.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use bitflags::bitflags;
+use ijson::{ijson, IValue};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{dataclasses::Snowflake, gateway::VoiceConnectionInfo};
+
+use super::{EncryptionMode, VoiceError};
+
+/// A payload of the voice gateway.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawVoiceEvent {
+    #[serde(rename = "op")]
+    pub op_code: u32,
+    #[serde(rename = "d")]
+    pub data: Option<IValue>,
+
+    /// The sequence number, on payloads sent by the server.
+    #[serde(rename = "seq", default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+}
+
+impl TryFrom<Message> for RawVoiceEvent {
+    type Error = VoiceError;
+
+    fn try_from(value: Message) -> Result<Self, Self::Error> {
+        let decoded = match &value {
+            Message::Text(text) => serde_json::from_str::<Self>(text),
+            Message::Close(frame) => return Err(VoiceError::closed(frame.as_ref())),
+            _ => return Err(VoiceError::UnexpectedFrame(value)),
+        };
+
+        decoded.map_err(|source| VoiceError::Decode {
+            source: source.into(),
+            raw: value,
+        })
+    }
+}
+
+impl From<RawVoiceEvent> for Message {
+    fn from(val: RawVoiceEvent) -> Self {
+        Message::Text(serde_json::to_string(&val).unwrap())
+    }
+}
+
+impl RawVoiceEvent {
+    fn new(op_code: u32, data: IValue) -> Self {
+        Self {
+            op_code,
+            data: Some(data),
+            sequence: None,
+        }
+    }
+
+    /// Creates a new "Identify" structure. (op code: 0)
+    pub fn new_identify(info: &VoiceConnectionInfo) -> Self {
+        Self::new(
+            0,
+            ijson!({
+                "server_id": info.guild_id,
+                "user_id": info.user_id,
+                "session_id": info.session_id,
+                "token": info.token,
+            }),
+        )
+    }
+
+    /// Creates a new "Select Protocol" structure, with the external address of the UDP socket.
+    /// (op code: 1)
+    pub fn new_select_protocol(address: &str, port: u16, mode: EncryptionMode) -> Self {
+        Self::new(
+            1,
+            ijson!({
+                "protocol": "udp",
+                "data": {
+                    "address": address,
+                    "port": port,
+                    "mode": mode.as_str(),
+                },
+            }),
+        )
+    }
+
+    /// Creates a new "Heartbeat" structure. (op code: 3)
+    /// # Arguments
+    /// * `nonce` - Echoed back by the heartbeat ACK.
+    /// * `sequence_ack` - The sequence number of the last payload received.
+    pub fn new_heartbeat(nonce: u64, sequence_ack: Option<u64>) -> Self {
+        Self::new(
+            3,
+            ijson!({
+                "t": nonce,
+                "seq_ack": sequence_ack,
+            }),
+        )
+    }
+
+    /// Creates a new "Speaking" structure. Required before sending audio. (op code: 5)
+    pub fn new_speaking(speaking: SpeakingFlags, ssrc: u32) -> Self {
+        Self::new(
+            5,
+            ijson!({
+                "speaking": speaking.bits(),
+                "delay": 0,
+                "ssrc": ssrc,
+            }),
+        )
+    }
+
+    /// Converts the payload into a [`VoiceEvent`].
+    pub fn get_event_data(&self) -> Result<VoiceEvent> {
+        Ok(match self.op_code {
+            5 => VoiceEvent::Speaking(self.data()?),
+            11 => VoiceEvent::ClientsConnect(self.data()?),
+            13 => VoiceEvent::ClientDisconnect(self.data()?),
+            op_code => VoiceEvent::Unknown {
+                op_code,
+                data: self.data.clone().unwrap_or_default(),
+            },
+        })
+    }
+
+    /// Decodes the data of the payload.
+    pub fn data<T: for<'de> Deserialize<'de>>(&self) -> Result<T> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("no data (op code {})", self.op_code))?;
+
+        Ok(ijson::from_value(data)?)
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    /// How a user is speaking. (`u8`)
+    pub struct SpeakingFlags: u8 {
+        /// Normal transmission of voice audio.
+        const MICROPHONE = 1 << 0;
+        /// Transmission of context audio for video, no speaking indicator.
+        const SOUNDSHARE = 1 << 1;
+        /// Priority speaker, lowering the audio of other speakers.
+        const PRIORITY = 1 << 2;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceHelloData {
+    /// In milliseconds, may be fractional.
+    pub heartbeat_interval: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VoiceReadyData {
+    /// The synchronization source of the audio sent by the bot.
+    pub ssrc: u32,
+
+    /// The address of the UDP voice server.
+    pub ip: String,
+    pub port: u16,
+
+    /// The supported encryption modes.
+    pub modes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionDescriptionData {
+    pub mode: String,
+
+    /// The key to encrypt and decrypt RTP payloads with.
+    pub secret_key: Vec<u8>,
+}
+
+/// Events of the voice gateway, other than the handshake and heartbeats.
+#[derive(Debug)]
+pub enum VoiceEvent {
+    /// A user started or stopped speaking. Maps their SSRC to their user ID.
+    Speaking(SpeakingData),
+    /// Users joined the voice channel.
+    ClientsConnect(ClientsConnectData),
+    /// A user left the voice channel.
+    ClientDisconnect(ClientDisconnectData),
+    /// Any other op code.
+    Unknown { op_code: u32, data: IValue },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpeakingData {
+    pub user_id: Snowflake,
+    pub ssrc: u32,
+
+    /// See [`SpeakingFlags`].
+    pub speaking: u8,
+}
+
+impl SpeakingData {
+    pub fn flags(&self) -> SpeakingFlags {
+        SpeakingFlags::from_bits_truncate(self.speaking)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientsConnectData {
+    pub user_ids: Vec<Snowflake>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientDisconnectData {
+    pub user_id: Snowflake,
+}
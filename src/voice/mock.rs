@@ -0,0 +1,124 @@
+//! A local voice server and Ogg pages, for tests.
+
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, UdpSocket},
+    sync::mpsc::{self, UnboundedReceiver},
+};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{dataclasses::Snowflake, gateway::VoiceConnectionInfo};
+
+use super::connection::MAX_PACKET_SIZE;
+
+pub(crate) const SSRC: u32 = 77;
+pub(crate) const KEY: [u8; 32] = [7; 32];
+
+pub(crate) fn info() -> VoiceConnectionInfo {
+    VoiceConnectionInfo {
+        guild_id: Snowflake::new(1093210345113464883),
+        channel_id: Some(Snowflake::new(1093210345113464887)),
+        user_id: Snowflake::new(1093210012486668388),
+        session_id: "3f5c8a1b2d4e6f70".to_string(),
+        endpoint: "localhost".to_string(),
+        token: "voice token".to_string(),
+    }
+}
+
+/// A voice server that checks the handshake, and forwards the audio packets it receives.
+/// Heartbeats are only acknowledged if `ack` is set. `clients` are announced as connected
+/// after the session description. Its UDP socket is returned to send audio to the bot.
+pub(crate) async fn mock_server(
+    heartbeat_interval: f64,
+    ack: bool,
+    clients: Vec<Snowflake>,
+) -> (String, UnboundedReceiver<Vec<u8>>, Arc<UdpSocket>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/?v=8", listener.local_addr().unwrap());
+    let udp = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+    let udp_port = udp.local_addr().unwrap().port();
+    let (packets, packets_rx) = mpsc::unbounded_channel();
+
+    let socket = udp.clone();
+    tokio::spawn(async move {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        while let Ok((size, from)) = udp.recv_from(&mut buffer).await {
+            if size != 74 || buffer[..2] != 1u16.to_be_bytes() {
+                packets.send(buffer[..size].to_vec()).ok();
+                continue;
+            }
+
+            assert_eq!(buffer[4..8], SSRC.to_be_bytes());
+            let mut response = [0; 74];
+            response[..2].copy_from_slice(&2u16.to_be_bytes());
+            response[2..4].copy_from_slice(&70u16.to_be_bytes());
+            response[4..8].copy_from_slice(&SSRC.to_be_bytes());
+            response[8..15].copy_from_slice(b"1.2.3.4");
+            response[72..].copy_from_slice(&5555u16.to_be_bytes());
+            udp.send_to(&response, from).await.unwrap();
+        }
+    });
+
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        let hello = json!({"op": 8, "d": {"heartbeat_interval": heartbeat_interval}});
+        ws.send(Message::Text(hello.to_string())).await.unwrap();
+
+        while let Some(Ok(Message::Text(text))) = ws.next().await {
+            let event = serde_json::from_str::<Value>(&text).unwrap();
+            let data = &event["d"];
+
+            let reply = match event["op"].as_u64() {
+                Some(0) => {
+                    assert_eq!(data["server_id"], "1093210345113464883");
+                    assert_eq!(data["user_id"], "1093210012486668388");
+                    assert_eq!(data["session_id"], "3f5c8a1b2d4e6f70");
+                    assert_eq!(data["token"], "voice token");
+
+                    json!({"op": 2, "seq": 1, "d": {
+                        "ssrc": SSRC,
+                        "ip": "127.0.0.1",
+                        "port": udp_port,
+                        "modes": ["aead_xchacha20_poly1305_rtpsize", "aead_aes256_gcm_rtpsize"],
+                    }})
+                }
+                Some(1) => {
+                    assert_eq!(data["protocol"], "udp");
+                    assert_eq!(data["data"]["address"], "1.2.3.4");
+                    assert_eq!(data["data"]["port"], 5555);
+                    assert_eq!(data["data"]["mode"], "aead_aes256_gcm_rtpsize");
+
+                    json!({"op": 4, "seq": 2, "d": {
+                        "mode": "aead_aes256_gcm_rtpsize",
+                        "secret_key": KEY,
+                    }})
+                }
+                Some(3) if ack => json!({"op": 6, "seq": 3, "d": {"t": data["t"]}}),
+                _ => continue,
+            };
+            ws.send(Message::Text(reply.to_string())).await.unwrap();
+
+            if reply["op"] == 4 && !clients.is_empty() {
+                let connect = json!({"op": 11, "seq": 3, "d": {"user_ids": clients}});
+                ws.send(Message::Text(connect.to_string())).await.unwrap();
+            }
+        }
+    });
+
+    (url, packets_rx, socket)
+}
+
+/// An Ogg page holding `segments`, of at most 255 bytes each.
+pub(crate) fn ogg_page(segments: &[&[u8]]) -> Vec<u8> {
+    let mut page = b"OggS".to_vec();
+    // version, flags, granule position, serial number, sequence number and checksum
+    page.resize(26, 0);
+    page.push(segments.len() as u8);
+    page.extend(segments.iter().map(|segment| segment.len() as u8));
+    page.extend(segments.concat());
+    page
+}
@@ -0,0 +1,26 @@
+pub mod close_code;
+pub mod connection;
+pub mod crypto;
+pub mod error;
+pub mod event;
+pub mod ogg;
+pub mod player;
+pub mod receive;
+pub mod rtp;
+pub mod track;
+pub mod udp;
+
+#[cfg(test)]
+mod mock;
+
+pub use close_code::*;
+pub use connection::*;
+pub use crypto::*;
+pub use error::*;
+pub use event::*;
+pub use ogg::*;
+pub use player::*;
+pub use receive::*;
+pub use rtp::*;
+pub use track::*;
+pub use udp::*;
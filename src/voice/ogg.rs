@@ -0,0 +1,159 @@
+use std::{collections::VecDeque, io::ErrorKind};
+
+use anyhow::Result;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The size of an Ogg page header, before the segment table.
+const PAGE_HEADER_SIZE: usize = 27;
+
+/// Reads the Opus packets of an Ogg/Opus stream, without decoding them.
+///
+/// The `OpusHead` and `OpusTags` headers are skipped, including those of chained streams.
+/// Page checksums aren't verified.
+pub struct OggOpusReader<R> {
+    reader: R,
+
+    /// Complete packets of the pages read so far.
+    packets: VecDeque<Vec<u8>>,
+
+    /// A packet continued on the next page.
+    partial: Vec<u8>,
+    started: bool,
+}
+
+impl<R: AsyncRead + Unpin> OggOpusReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            packets: VecDeque::new(),
+            partial: Vec::new(),
+            started: false,
+        }
+    }
+
+    /// The next Opus packet, or `None` at the end of the stream.
+    pub async fn next_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            while let Some(packet) = self.packets.pop_front() {
+                if packet.starts_with(b"OpusHead") {
+                    self.started = true;
+                } else if !self.started {
+                    return Err(anyhow::anyhow!("not an Ogg/Opus stream"));
+                } else if !packet.starts_with(b"OpusTags") {
+                    return Ok(Some(packet));
+                }
+            }
+
+            if !self.read_page().await? {
+                if !self.started {
+                    return Err(anyhow::anyhow!("not an Ogg/Opus stream"));
+                }
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Reads a page, splitting it into packets. Returns `false` at the end of the stream.
+    async fn read_page(&mut self) -> Result<bool> {
+        let mut header = [0; PAGE_HEADER_SIZE];
+        match self.reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+            Err(err) => return Err(err.into()),
+        }
+
+        if &header[..4] != b"OggS" {
+            return Err(anyhow::anyhow!("invalid Ogg page"));
+        }
+
+        let mut segments = vec![0; header[26] as usize];
+        self.reader.read_exact(&mut segments).await?;
+
+        let mut data = vec![0; segments.iter().map(|size| *size as usize).sum()];
+        self.reader.read_exact(&mut data).await?;
+
+        // a packet is made of segments of 255 bytes, ending with a shorter one
+        let mut offset = 0;
+        for size in segments {
+            let size = size as usize;
+            self.partial.extend_from_slice(&data[offset..offset + size]);
+            offset += size;
+
+            if size < 255 {
+                self.packets.push_back(std::mem::take(&mut self.partial));
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::mock::ogg_page as page;
+
+    /// The segments of a packet: 255 bytes each, ending with a shorter one.
+    fn lace(packet: &[u8]) -> Vec<&[u8]> {
+        let mut segments = packet.chunks(255).collect::<Vec<_>>();
+        if packet.len().is_multiple_of(255) {
+            segments.push(&[]);
+        }
+        segments
+    }
+
+    fn headers() -> Vec<u8> {
+        [page(&[b"OpusHead\x01\x02"]), page(&[b"OpusTags"])].concat()
+    }
+
+    async fn packets(stream: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let mut reader = OggOpusReader::new(stream);
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().await? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    #[tokio::test]
+    async fn skips_headers() {
+        let stream = [headers(), page(&[&[1, 2], &[3]]), page(&[&[4]])].concat();
+        assert_eq!(
+            packets(&stream).await.unwrap(),
+            [vec![1, 2], vec![3], vec![4]]
+        );
+    }
+
+    #[tokio::test]
+    async fn joins_packets_across_pages() {
+        // a packet continued on the next page, and one ending with an empty segment
+        let long = vec![7; 300];
+        let segments = lace(&long);
+        let exact = vec![8; 255];
+        let stream = [
+            headers(),
+            page(&segments[..1]),
+            page(&[segments[1], &[9]]),
+            page(&lace(&exact)),
+        ]
+        .concat();
+
+        assert_eq!(packets(&stream).await.unwrap(), [long, vec![9], exact]);
+    }
+
+    #[tokio::test]
+    async fn fails_on_truncated_page() {
+        let stream = [headers(), page(&[&[1]]), page(&[&[2, 3]])].concat();
+        let mut reader = OggOpusReader::new(&stream[..stream.len() - 1]);
+
+        assert_eq!(reader.next_packet().await.unwrap(), Some(vec![1]));
+        assert!(reader.next_packet().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_other_streams() {
+        let stream = page(&[b"OpusTags"]);
+        assert!(packets(&stream).await.is_err());
+        assert!(packets(b"RIFF").await.is_err());
+    }
+}
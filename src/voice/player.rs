@@ -0,0 +1,380 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::dataclasses::Snowflake;
+
+use super::{
+    SpeakingFlags, Track, TrackEndData, TrackEndReason, VoiceConnection, SILENCE_FRAME,
+    SILENCE_FRAMES,
+};
+
+pub type PlayerTx = UnboundedSender<TrackEndData>;
+pub type PlayerRx = UnboundedReceiver<TrackEndData>;
+
+/// The duration of an Opus frame.
+const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+/// Commands for the player task.
+enum PlayerCommand {
+    Enqueue(Track),
+    Pause,
+    Resume,
+    Skip,
+    Stop,
+}
+
+/// Plays a queue of [`Track`]s on a voice connection, one frame every 20 ms.
+///
+/// Playback happens on a separate task, stopped when the player is dropped.
+/// Each track that stops playing is reported as a [`TrackEndData`].
+///
+/// # Example
+/// ```rust,no_run
+/// use std::sync::Arc;
+///
+/// use omu::{
+///     voice::{Track, VoiceConnection},
+///     Client, ClientEvent,
+/// };
+/// # use omu::gateway::VoiceConnectionInfo;
+/// # async fn example(mut client: Client, info: VoiceConnectionInfo) -> anyhow::Result<()> {
+///
+/// let voice = Arc::new(VoiceConnection::connect(info).await?);
+/// let player = client.new_player(voice.clone());
+/// player.enqueue(Track::open("song.ogg").await?)?;
+///
+/// if let ClientEvent::TrackEnd(end) = client.next_event().await? {
+///     println!("{:?} ended: {:?}", end.name, end.reason);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioPlayer {
+    pub voice: Arc<VoiceConnection>,
+    commands: UnboundedSender<PlayerCommand>,
+}
+
+impl AudioPlayer {
+    /// Starts a player on `voice`, reporting the tracks that end to `events`.
+    pub fn new(voice: Arc<VoiceConnection>, events: PlayerTx) -> Self {
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(Self::player_task(voice.clone(), commands_rx, events));
+
+        Self { voice, commands }
+    }
+
+    pub fn guild_id(&self) -> Snowflake {
+        self.voice.info.guild_id
+    }
+
+    /// Adds a track to the end of the queue. Plays right away if nothing is playing.
+    pub fn enqueue(&self, track: Track) -> Result<()> {
+        self.send(PlayerCommand::Enqueue(track))
+    }
+
+    pub fn pause(&self) -> Result<()> {
+        self.send(PlayerCommand::Pause)
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.send(PlayerCommand::Resume)
+    }
+
+    /// Stops the current track, and plays the next one.
+    pub fn skip(&self) -> Result<()> {
+        self.send(PlayerCommand::Skip)
+    }
+
+    /// Stops the current track, and clears the queue.
+    pub fn stop(&self) -> Result<()> {
+        self.send(PlayerCommand::Stop)
+    }
+
+    fn send(&self, command: PlayerCommand) -> Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow::anyhow!("Player stopped"))
+    }
+
+    async fn player_task(
+        voice: Arc<VoiceConnection>,
+        mut commands: UnboundedReceiver<PlayerCommand>,
+        events: PlayerTx,
+    ) {
+        let guild_id = voice.info.guild_id;
+        let end = |track: Track, reason: TrackEndReason| {
+            events
+                .send(TrackEndData {
+                    guild_id,
+                    track_id: track.id,
+                    name: track.name,
+                    reason,
+                })
+                .ok();
+        };
+
+        let mut queue = VecDeque::new();
+        let mut current: Option<Track> = None;
+        let mut paused = false;
+        let mut closed = false;
+
+        // frames of silence left to send after the audio stops, paced like the audio
+        let mut speaking = false;
+        let mut silence = 0;
+
+        let mut ticks = interval(FRAME_DURATION);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            if current.is_none() {
+                if let Some(track) = queue.pop_front() {
+                    current = Some(track);
+                    ticks.reset();
+                }
+            }
+            let playing = current.is_some() && !paused;
+            let silent = speaking && silence > 0;
+            if closed && !silent {
+                break;
+            }
+
+            tokio::select! {
+                command = commands.recv(), if !closed => match command {
+                    Some(PlayerCommand::Enqueue(track)) => queue.push_back(track),
+                    Some(PlayerCommand::Pause) => {
+                        paused = true;
+                        silence = SILENCE_FRAMES;
+                    }
+                    Some(PlayerCommand::Resume) => {
+                        paused = false;
+                        ticks.reset();
+                    }
+                    Some(PlayerCommand::Skip) => {
+                        if let Some(track) = current.take() {
+                            end(track, TrackEndReason::Skipped);
+                        }
+                        if queue.is_empty() {
+                            silence = SILENCE_FRAMES;
+                        }
+                    }
+                    Some(PlayerCommand::Stop) => {
+                        queue.clear();
+                        if let Some(track) = current.take() {
+                            end(track, TrackEndReason::Stopped);
+                        }
+                        silence = SILENCE_FRAMES;
+                    }
+                    None => {
+                        closed = true;
+                        queue.clear();
+                        current = None;
+                        silence = SILENCE_FRAMES;
+                    }
+                },
+                _ = ticks.tick(), if playing || silent => {
+                    let Some(track) = current.as_mut().filter(|_| playing) else {
+                        silence -= 1;
+                        voice.send_opus(&SILENCE_FRAME).await.ok();
+                        if silence == 0 {
+                            voice.set_speaking(SpeakingFlags::empty()).await.ok();
+                            speaking = false;
+                        }
+                        continue;
+                    };
+
+                    let reason = match track.next_frame().await {
+                        Ok(Some(frame)) => match voice.send_opus(&frame).await {
+                            Ok(()) => {
+                                speaking = true;
+                                silence = 0;
+                                continue;
+                            }
+                            Err(err) => TrackEndReason::Error(err.to_string()),
+                        },
+                        Ok(None) => TrackEndReason::Finished,
+                        Err(err) => TrackEndReason::Error(err.to_string()),
+                    };
+
+                    if let Some(track) = current.take() {
+                        end(track, reason);
+                    }
+                    if queue.is_empty() {
+                        silence = SILENCE_FRAMES;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, time::Instant};
+
+    use futures_util::stream;
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::voice::{
+        mock::{info, mock_server, ogg_page, KEY},
+        EncryptionMode, RtpPacket, VoiceCipher,
+    };
+
+    type Packets = UnboundedReceiver<Vec<u8>>;
+
+    /// A player on a mock voice server, with its events and the packets it sends.
+    async fn player() -> (AudioPlayer, PlayerRx, Packets) {
+        let (url, packets, _) = mock_server(30000.0, true, vec![]).await;
+        let voice = VoiceConnection::connect_to(&url, info()).await.unwrap();
+        let (events, events_rx) = mpsc::unbounded_channel();
+
+        (
+            AudioPlayer::new(Arc::new(voice), events),
+            events_rx,
+            packets,
+        )
+    }
+
+    /// An in-memory Ogg/Opus track.
+    fn ogg(frames: &[&[u8]]) -> Track {
+        let stream = [
+            ogg_page(&[b"OpusHead"]),
+            ogg_page(&[b"OpusTags"]),
+            ogg_page(frames),
+        ]
+        .concat();
+        Track::from_ogg(Cursor::new(stream))
+    }
+
+    /// A track that doesn't end.
+    fn endless() -> Track {
+        Track::from_opus(stream::repeat(vec![1]))
+    }
+
+    /// The decrypted payload of the next packet.
+    async fn frame(packets: &mut Packets) -> Vec<u8> {
+        let packet = timeout(Duration::from_secs(5), packets.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let cipher = VoiceCipher::new(EncryptionMode::Aes256Gcm, &KEY).unwrap();
+        cipher.decrypt(&RtpPacket::parse(&packet).unwrap()).unwrap()
+    }
+
+    /// Skips the frames still playing, then checks that silence follows, and nothing after it.
+    async fn expect_silence(packets: &mut Packets) {
+        let mut received = frame(packets).await;
+        while received != SILENCE_FRAME {
+            received = frame(packets).await;
+        }
+        for _ in 1..SILENCE_FRAMES {
+            assert_eq!(frame(packets).await, SILENCE_FRAME);
+        }
+        assert!(timeout(FRAME_DURATION * 5, packets.recv()).await.is_err());
+    }
+
+    async fn end(events: &mut PlayerRx) -> TrackEndData {
+        timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn plays_queue() {
+        let (player, mut events, mut packets) = player().await;
+        let first = ogg(&[&[1], &[2]]);
+        let second = Track::from_opus(stream::iter([vec![3]])).with_name("second");
+        let ids = [first.id, second.id];
+        player.enqueue(first).unwrap();
+        player.enqueue(second).unwrap();
+
+        for expected in [[1], [2], [3]] {
+            assert_eq!(frame(&mut packets).await, expected);
+        }
+
+        // paced like the audio, rather than sent at once
+        let started = Instant::now();
+        for _ in 0..SILENCE_FRAMES {
+            assert_eq!(frame(&mut packets).await, SILENCE_FRAME);
+        }
+        assert!(started.elapsed() >= FRAME_DURATION * (SILENCE_FRAMES as u32 - 1));
+
+        let end_first = end(&mut events).await;
+        assert_eq!(end_first.track_id, ids[0]);
+        assert_eq!(end_first.reason, TrackEndReason::Finished);
+        let end_second = end(&mut events).await;
+        assert_eq!(end_second.track_id, ids[1]);
+        assert_eq!(end_second.name.as_deref(), Some("second"));
+        assert_eq!(end_second.reason, TrackEndReason::Finished);
+    }
+
+    #[tokio::test]
+    async fn skips_to_next_track() {
+        let (player, mut events, mut packets) = player().await;
+        let first = endless();
+        let id = first.id;
+        player.enqueue(first).unwrap();
+        player.enqueue(ogg(&[&[2]])).unwrap();
+
+        assert_eq!(frame(&mut packets).await, [1]);
+        player.skip().unwrap();
+
+        let end_first = end(&mut events).await;
+        assert_eq!(end_first.track_id, id);
+        assert_eq!(end_first.reason, TrackEndReason::Skipped);
+
+        // no silence between the tracks
+        let mut next = frame(&mut packets).await;
+        while next == [1] {
+            next = frame(&mut packets).await;
+        }
+        assert_eq!(next, [2]);
+        assert_eq!(end(&mut events).await.reason, TrackEndReason::Finished);
+        expect_silence(&mut packets).await;
+    }
+
+    #[tokio::test]
+    async fn stop_clears_queue() {
+        let (player, mut events, mut packets) = player().await;
+        player.enqueue(endless()).unwrap();
+        player.enqueue(endless()).unwrap();
+
+        assert_eq!(frame(&mut packets).await, [1]);
+        player.stop().unwrap();
+
+        assert_eq!(end(&mut events).await.reason, TrackEndReason::Stopped);
+        expect_silence(&mut packets).await;
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn pauses_and_resumes() {
+        let (player, mut events, mut packets) = player().await;
+        player.enqueue(endless()).unwrap();
+
+        assert_eq!(frame(&mut packets).await, [1]);
+        player.pause().unwrap();
+        expect_silence(&mut packets).await;
+
+        player.resume().unwrap();
+        assert_eq!(frame(&mut packets).await, [1]);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn drop_stops_playing() {
+        let (player, _events, mut packets) = player().await;
+        player.enqueue(endless()).unwrap();
+
+        assert_eq!(frame(&mut packets).await, [1]);
+        drop(player);
+        expect_silence(&mut packets).await;
+    }
+}
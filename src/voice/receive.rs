@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use crate::dataclasses::Snowflake;
+
+/// How many packets may be held back waiting for a missing one, 100 ms of audio.
+const JITTER_DEPTH: usize = 5;
+
+/// Sequence numbers further apart than this are a new stream, rather than a gap.
+const MAX_GAP: i16 = 64;
+
+/// A decrypted Opus packet received from a user.
+#[derive(Debug, Clone)]
+pub struct VoicePacket {
+    pub user_id: Snowflake,
+    pub ssrc: u32,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub opus: Vec<u8>,
+}
+
+/// Reorders the packets of one SSRC by sequence number.
+///
+/// Packets in order go through right away. After a gap, later packets are held until the
+/// missing one arrives, or until more than [`JITTER_DEPTH`] are held and the gap is skipped.
+/// Packets older than the ones already handed out are dropped.
+#[derive(Default)]
+pub(crate) struct JitterBuffer {
+    /// The sequence number of `slots[0]`.
+    next: Option<u16>,
+    slots: VecDeque<Option<VoicePacket>>,
+    held: usize,
+}
+
+impl JitterBuffer {
+    /// Adds a packet, returning the packets that are now in order.
+    pub(crate) fn push(&mut self, packet: VoicePacket) -> Vec<VoicePacket> {
+        let mut ready = Vec::new();
+        let next = *self.next.get_or_insert(packet.sequence);
+
+        // sequence numbers wrap around, and jump when the stream starts over
+        let offset = packet.sequence.wrapping_sub(next) as i16;
+        if (-MAX_GAP..0).contains(&offset) {
+            return ready;
+        }
+        if !(0..MAX_GAP).contains(&offset) {
+            while !self.slots.is_empty() {
+                self.advance(&mut ready);
+            }
+            self.next = Some(packet.sequence);
+        }
+
+        let offset = packet.sequence.wrapping_sub(self.next.unwrap_or_default()) as usize;
+        if self.slots.len() <= offset {
+            self.slots.resize(offset + 1, None);
+        }
+        if self.slots[offset].is_none() {
+            self.slots[offset] = Some(packet);
+            self.held += 1;
+        }
+
+        loop {
+            match self.slots.front() {
+                Some(Some(_)) => self.advance(&mut ready),
+                Some(None) if self.held > JITTER_DEPTH => self.advance(&mut ready),
+                _ => break,
+            }
+        }
+
+        ready
+    }
+
+    /// Moves past the first slot, handing out its packet if received.
+    fn advance(&mut self, ready: &mut Vec<VoicePacket>) {
+        if let Some(Some(packet)) = self.slots.pop_front() {
+            ready.push(packet);
+            self.held -= 1;
+        }
+        self.next = self.next.map(|next| next.wrapping_add(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(sequence: u16) -> VoicePacket {
+        VoicePacket {
+            user_id: Snowflake::new(1),
+            ssrc: 1,
+            sequence,
+            timestamp: sequence as u32 * 960,
+            opus: vec![],
+        }
+    }
+
+    fn push(buffer: &mut JitterBuffer, sequence: u16) -> Vec<u16> {
+        buffer
+            .push(packet(sequence))
+            .iter()
+            .map(|p| p.sequence)
+            .collect()
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut buffer = JitterBuffer::default();
+        assert_eq!(push(&mut buffer, u16::MAX - 1), [u16::MAX - 1]);
+        assert_eq!(push(&mut buffer, 1), [] as [u16; 0]);
+        assert_eq!(push(&mut buffer, u16::MAX), [u16::MAX]);
+        assert_eq!(push(&mut buffer, 0), [0, 1]);
+        assert_eq!(push(&mut buffer, 2), [2]);
+    }
+
+    #[test]
+    fn skips_gap_when_too_many_held() {
+        let mut buffer = JitterBuffer::default();
+        assert_eq!(push(&mut buffer, 10), [10]);
+        for sequence in 12..12 + JITTER_DEPTH as u16 {
+            assert_eq!(push(&mut buffer, sequence), [] as [u16; 0]);
+        }
+        assert_eq!(push(&mut buffer, 17), [12, 13, 14, 15, 16, 17]);
+
+        // the skipped packet is late now
+        assert_eq!(push(&mut buffer, 11), [] as [u16; 0]);
+        assert_eq!(push(&mut buffer, 18), [18]);
+    }
+
+    #[test]
+    fn drops_late_and_duplicate_packets() {
+        let mut buffer = JitterBuffer::default();
+        assert_eq!(push(&mut buffer, 10), [10]);
+        assert_eq!(push(&mut buffer, 11), [11]);
+        assert_eq!(push(&mut buffer, 11), [] as [u16; 0]);
+        assert_eq!(push(&mut buffer, 9), [] as [u16; 0]);
+
+        assert_eq!(push(&mut buffer, 13), [] as [u16; 0]);
+        assert_eq!(push(&mut buffer, 13), [] as [u16; 0]);
+        assert_eq!(push(&mut buffer, 12), [12, 13]);
+    }
+
+    #[test]
+    fn resets_on_jump() {
+        let mut buffer = JitterBuffer::default();
+        assert_eq!(push(&mut buffer, 10), [10]);
+        assert_eq!(push(&mut buffer, 12), [] as [u16; 0]);
+
+        // held packets are handed out before the new stream starts
+        assert_eq!(push(&mut buffer, 1000), [12, 1000]);
+        assert_eq!(push(&mut buffer, 1001), [1001]);
+
+        // so is a packet far behind
+        assert_eq!(push(&mut buffer, 11), [11]);
+    }
+}
@@ -0,0 +1,168 @@
+use super::NONCE_SIZE;
+
+/// The sample rate of Opus audio sent to Discord.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// The samples per channel in a 20 ms Opus frame, the frame size Discord expects.
+pub const FRAME_SAMPLES: u32 = 960;
+
+/// An Opus frame of silence. [`SILENCE_FRAMES`] are sent when speaking stops, to avoid interpolation.
+pub const SILENCE_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+/// The number of silence frames sent when speaking stops.
+pub const SILENCE_FRAMES: usize = 5;
+
+/// The size of an RTP header without CSRCs or extension.
+pub const RTP_HEADER_SIZE: usize = 12;
+
+/// RTP version 2, without padding, extension or CSRCs.
+const RTP_VERSION: u8 = 0x80;
+
+/// The payload type of Opus audio.
+const RTP_PAYLOAD_TYPE: u8 = 0x78;
+
+/// The RTP header of an outgoing audio packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RtpHeader {
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+}
+
+impl RtpHeader {
+    pub fn to_bytes(&self) -> [u8; RTP_HEADER_SIZE] {
+        let mut header = [0; RTP_HEADER_SIZE];
+        header[0] = RTP_VERSION;
+        header[1] = RTP_PAYLOAD_TYPE;
+        header[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        header[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        header[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+        header
+    }
+}
+
+/// An incoming RTP packet, split into its parts. RTCP and other payload types are rejected.
+#[derive(Debug)]
+pub struct RtpPacket<'a> {
+    pub header: RtpHeader,
+
+    /// The authenticated part: the fixed header, CSRCs and the extension header.
+    pub aad: &'a [u8],
+
+    /// The encrypted payload, with its tag.
+    pub encrypted: &'a [u8],
+    pub nonce: u32,
+
+    /// The size of the extension, at the start of the decrypted payload.
+    pub extension_size: usize,
+
+    /// Whether the decrypted payload ends with padding.
+    pub padding: bool,
+}
+
+impl<'a> RtpPacket<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len() < RTP_HEADER_SIZE + NONCE_SIZE
+            || bytes[0] >> 6 != 2
+            || bytes[1] & 0x7F != RTP_PAYLOAD_TYPE
+        {
+            return None;
+        }
+
+        let padding = bytes[0] & 0x20 != 0;
+        let extension = bytes[0] & 0x10 != 0;
+        let csrc_count = (bytes[0] & 0x0F) as usize;
+
+        let mut aad_size = RTP_HEADER_SIZE + 4 * csrc_count;
+        let mut extension_size = 0;
+        if extension {
+            // the extension header is authenticated, its data is encrypted
+            let length = bytes.get(aad_size + 2..aad_size + 4)?;
+            extension_size = 4 * u16::from_be_bytes([length[0], length[1]]) as usize;
+            aad_size += 4;
+        }
+
+        let nonce_start = bytes.len().checked_sub(NONCE_SIZE)?;
+        if nonce_start < aad_size {
+            return None;
+        }
+
+        Some(Self {
+            header: RtpHeader {
+                sequence: u16::from_be_bytes([bytes[2], bytes[3]]),
+                timestamp: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+                ssrc: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            },
+            aad: &bytes[..aad_size],
+            encrypted: &bytes[aad_size..nonce_start],
+            nonce: u32::from_be_bytes(bytes[nonce_start..].try_into().ok()?),
+            extension_size,
+            padding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voice::{EncryptionMode, VoiceCipher};
+
+    const HEADER: RtpHeader = RtpHeader {
+        sequence: 7,
+        timestamp: 6720,
+        ssrc: 42,
+    };
+
+    fn cipher() -> VoiceCipher {
+        VoiceCipher::new(EncryptionMode::Aes256Gcm, &[7; 32]).unwrap()
+    }
+
+    #[test]
+    fn parses_header() {
+        let packet = cipher().encrypt(&HEADER.to_bytes(), b"opus", 3).unwrap();
+        let parsed = RtpPacket::parse(&packet).unwrap();
+
+        assert_eq!(parsed.header, HEADER);
+        assert_eq!(parsed.aad, HEADER.to_bytes());
+        assert_eq!(parsed.nonce, 3);
+        assert_eq!(parsed.extension_size, 0);
+        assert!(!parsed.padding);
+        assert_eq!(cipher().decrypt(&parsed).unwrap(), b"opus");
+    }
+
+    #[test]
+    fn strips_extension_and_padding() {
+        // a one-word extension and a CSRC, with padding
+        let mut aad = HEADER.to_bytes().to_vec();
+        aad[0] |= 0x20 | 0x10 | 1;
+        aad.extend_from_slice(&[0, 0, 0, 9]);
+        aad.extend_from_slice(&[0xBE, 0xDE, 0, 1]);
+        let payload = [&[1, 2, 3, 4][..], b"opus", &[0, 0, 3]].concat();
+
+        let packet = cipher().encrypt(&aad, &payload, 3).unwrap();
+        let parsed = RtpPacket::parse(&packet).unwrap();
+
+        // rtpsize: the extension header is authenticated, its data is encrypted
+        assert_eq!(parsed.header, HEADER);
+        assert_eq!(parsed.aad, aad);
+        assert_eq!(parsed.extension_size, 4);
+        assert!(parsed.padding);
+        assert_eq!(cipher().decrypt(&parsed).unwrap(), b"opus");
+    }
+
+    #[test]
+    fn rejects_other_packets() {
+        let packet = cipher().encrypt(&HEADER.to_bytes(), b"opus", 3).unwrap();
+
+        let mut rtcp = packet.clone();
+        rtcp[1] = 0xC9;
+        assert!(RtpPacket::parse(&rtcp).is_none());
+        assert!(RtpPacket::parse(&packet[..RTP_HEADER_SIZE + 2]).is_none());
+
+        // an extension header cut off by the nonce
+        let mut truncated = HEADER.to_bytes().to_vec();
+        truncated[0] |= 0x10;
+        truncated.extend_from_slice(&[0xBE, 0xDE, 0, 1]);
+        assert!(RtpPacket::parse(&truncated).is_none());
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::Result;
+use futures_util::{stream::BoxStream, Stream, StreamExt};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, BufReader},
+};
+
+use crate::dataclasses::Snowflake;
+
+use super::OggOpusReader;
+
+static NEXT_TRACK_ID: AtomicU64 = AtomicU64::new(1);
+
+enum TrackSource {
+    Ogg(OggOpusReader<Box<dyn AsyncRead + Send + Unpin>>),
+    Opus(BoxStream<'static, Vec<u8>>),
+}
+
+/// Pre-encoded audio to play, as 20 ms Opus frames (48 kHz, stereo).
+/// The frames are sent as they are, there is no decoding or volume.
+pub struct Track {
+    /// Identifies the track in [`TrackEndData`].
+    pub id: u64,
+    pub name: Option<String>,
+
+    source: TrackSource,
+}
+
+impl Track {
+    fn new(source: TrackSource) -> Self {
+        Self {
+            id: NEXT_TRACK_ID.fetch_add(1, Ordering::Relaxed),
+            name: None,
+            source,
+        }
+    }
+
+    /// Opens an Ogg/Opus file, such as one made by
+    /// `ffmpeg -i input -c:a libopus -ar 48000 -ac 2 output.ogg`.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref()).await?;
+        let name = path.as_ref().to_string_lossy().to_string();
+
+        Ok(Self::from_ogg(BufReader::new(file)).with_name(name))
+    }
+
+    /// Plays an Ogg/Opus stream.
+    pub fn from_ogg<R: AsyncRead + Send + Unpin + 'static>(reader: R) -> Self {
+        Self::new(TrackSource::Ogg(OggOpusReader::new(Box::new(reader))))
+    }
+
+    /// Plays a stream of raw Opus frames.
+    pub fn from_opus<S: Stream<Item = Vec<u8>> + Send + 'static>(stream: S) -> Self {
+        Self::new(TrackSource::Opus(stream.boxed()))
+    }
+
+    pub fn with_name<K: ToString>(mut self, name: K) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// The next Opus frame, or `None` at the end of the track.
+    pub async fn next_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        match &mut self.source {
+            TrackSource::Ogg(reader) => reader.next_packet().await,
+            TrackSource::Opus(stream) => Ok(stream.next().await),
+        }
+    }
+}
+
+/// Why a track stopped playing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackEndReason {
+    /// Every frame was played.
+    Finished,
+    Skipped,
+    Stopped,
+    /// Reading or sending the track failed.
+    Error(String),
+}
+
+/// A track of an [`AudioPlayer`](super::AudioPlayer) stopped playing.
+#[derive(Debug, Clone)]
+pub struct TrackEndData {
+    pub guild_id: Snowflake,
+    pub track_id: u64,
+    pub name: Option<String>,
+    pub reason: TrackEndReason,
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::{net::UdpSocket, time::timeout};
+
+use super::VoiceError;
+
+/// The size of IP discovery packets, both ways.
+const DISCOVERY_SIZE: usize = 74;
+
+/// How long to wait for the IP discovery response.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Finds the external address and port of `socket`, as seen by the voice server it's connected to.
+/// They're sent back in "Select Protocol".
+pub async fn discover_ip(socket: &UdpSocket, ssrc: u32) -> Result<(String, u16)> {
+    // type 0x1 (request), the length of what follows (70), the ssrc, then room for the address and port
+    let mut request = [0; DISCOVERY_SIZE];
+    request[0..2].copy_from_slice(&1u16.to_be_bytes());
+    request[2..4].copy_from_slice(&70u16.to_be_bytes());
+    request[4..8].copy_from_slice(&ssrc.to_be_bytes());
+    socket.send(&request).await?;
+
+    let mut response = [0; DISCOVERY_SIZE];
+    let size = timeout(DISCOVERY_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| VoiceError::IpDiscovery("timed out"))??;
+
+    if size != DISCOVERY_SIZE || response[0..2] != 2u16.to_be_bytes() {
+        return Err(VoiceError::IpDiscovery("malformed response").into());
+    }
+
+    // a null-terminated string
+    let address = &response[8..72];
+    let end = address
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(address.len());
+    let address = std::str::from_utf8(&address[..end])
+        .map_err(|_| VoiceError::IpDiscovery("invalid address"))?;
+    let port = u16::from_be_bytes([response[72], response[73]]);
+
+    Ok((address.to_string(), port))
+}